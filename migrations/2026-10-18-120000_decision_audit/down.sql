@@ -0,0 +1,6 @@
+ALTER TABLE decision
+    DROP COLUMN decided_at,
+    DROP COLUMN decision_strategy_hash,
+    DROP COLUMN bureau_data,
+    DROP COLUMN outcome,
+    DROP COLUMN details;
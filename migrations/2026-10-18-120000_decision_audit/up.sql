@@ -0,0 +1,20 @@
+-- Decisions made before the audit columns existed get placeholder values:
+-- the migration time, an empty strategy hash, no bureau data and no details.
+-- Their outcome is recovered from the stored result where there is one.
+ALTER TABLE decision
+    ADD COLUMN decided_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN decision_strategy_hash VARCHAR NOT NULL DEFAULT '',
+    ADD COLUMN bureau_data JSONB NOT NULL DEFAULT '{}',
+    ADD COLUMN outcome VARCHAR NOT NULL DEFAULT '',
+    ADD COLUMN details JSONB NOT NULL DEFAULT '{}';
+
+UPDATE decision
+    SET outcome = result ->> 'result'
+    WHERE result ->> 'result' IS NOT NULL;
+
+ALTER TABLE decision
+    ALTER COLUMN decided_at DROP DEFAULT,
+    ALTER COLUMN decision_strategy_hash DROP DEFAULT,
+    ALTER COLUMN bureau_data DROP DEFAULT,
+    ALTER COLUMN outcome DROP DEFAULT,
+    ALTER COLUMN details DROP DEFAULT;
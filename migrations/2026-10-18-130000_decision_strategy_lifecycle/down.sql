@@ -0,0 +1,3 @@
+ALTER TABLE decision_strategy
+    DROP COLUMN created_at,
+    DROP COLUMN retired_at;
@@ -0,0 +1,3 @@
+ALTER TABLE decision_strategy
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN retired_at TIMESTAMPTZ;
@@ -0,0 +1,147 @@
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::{DecisionStrategy, Strategy};
+use diesel::pg::PgConnection;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Deserialized strategy along with the hash of the JSON it was loaded from.
+pub struct CachedStrategy {
+    pub strategy: Strategy,
+    pub content_hash: String,
+}
+
+/// Deserialized strategies by `decision_strategy_id`, so that each is loaded
+/// from the database and deserialized once rather than on every decision.
+/// The JSON of a stored strategy is never modified, so the only way a cached
+/// strategy goes stale is by being retired, which must be followed by
+/// `remove`. Retirements made by other servers are not seen until they
+/// restart.
+pub struct StrategyCache {
+    strategies: RwLock<HashMap<i32, Arc<CachedStrategy>>>,
+}
+
+impl StrategyCache {
+    pub fn new() -> Self {
+        StrategyCache {
+            strategies: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the strategy with the given id, loading it on first use.
+    /// Retired strategies are an error.
+    pub fn get(
+        &self,
+        id: i32,
+        connection: &PgConnection,
+    ) -> Result<Arc<CachedStrategy>, DecisionEngineError> {
+        self.get_or_load(id, || DecisionStrategy::from_id(id, connection))
+    }
+
+    fn get_or_load<F>(&self, id: i32, load: F) -> Result<Arc<CachedStrategy>, DecisionEngineError>
+    where
+        F: FnOnce() -> Result<DecisionStrategy, DecisionEngineError>,
+    {
+        // A panic while holding the lock cannot leave the map inconsistent,
+        // so a poisoned lock is still safe to use.
+        if let Some(strategy) = self
+            .strategies
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&id)
+        {
+            return Ok(strategy.clone());
+        }
+
+        // Concurrent misses may each load the strategy; the first one stored
+        // is kept.
+        let decision_strategy = load()?;
+        if decision_strategy.is_retired() {
+            return Err(DecisionEngineError::StrategyRetired(id));
+        }
+        let strategy = Arc::new(CachedStrategy {
+            strategy: decision_strategy.get_strategy()?,
+            content_hash: decision_strategy.content_hash(),
+        });
+        Ok(self
+            .strategies
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(id)
+            .or_insert(strategy)
+            .clone())
+    }
+
+    /// Forgets the strategy with the given id, so that it is reloaded on its
+    /// next use.
+    pub fn remove(&self, id: i32) {
+        self.strategies
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use decisionengine::testing::{rule_strategy, stored};
+    use serde_json::Value;
+    use std::cell::Cell;
+
+    #[test]
+    fn strategies_are_loaded_once() {
+        let cache = StrategyCache::new();
+        let loads = Cell::new(0);
+        let load = || {
+            loads.set(loads.get() + 1);
+            Ok(stored(rule_strategy(Value::from("true"))))
+        };
+        let first = cache.get_or_load(1, &load).unwrap();
+        let second = cache.get_or_load(1, &load).unwrap();
+        assert_eq!(loads.get(), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(
+            first.content_hash,
+            stored(rule_strategy(Value::from("true"))).content_hash()
+        );
+
+        cache.remove(1);
+        cache.get_or_load(1, &load).unwrap();
+        assert_eq!(loads.get(), 2);
+    }
+
+    #[test]
+    fn retired_and_invalid_strategies_are_not_cached() {
+        let cache = StrategyCache::new();
+        let mut retired = stored(rule_strategy(Value::from("true")));
+        retired.retired_at = Some(Utc::now());
+        match cache.get_or_load(1, || Ok(retired)) {
+            Err(DecisionEngineError::StrategyRetired(1)) => {}
+            _ => panic!("expected the strategy to be retired"),
+        }
+        match cache.get_or_load(1, || Ok(stored(json!({"type": "module"})))) {
+            Err(DecisionEngineError::InvalidStrategy(_)) => {}
+            _ => panic!("expected the strategy to be invalid"),
+        }
+        assert!(cache
+            .get_or_load(1, || Ok(stored(rule_strategy(Value::from("true")))))
+            .is_ok());
+    }
+
+    #[test]
+    fn stored_strategies_are_type_checked() {
+        let cache = StrategyCache::new();
+        let strategy = rule_strategy(Value::from("application_data_v1.age + 1"));
+        match cache.get_or_load(1, || Ok(stored(strategy))) {
+            Err(DecisionEngineError::InvalidStrategy(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(
+                    errors[0].message,
+                    "Expected Boolean expression, got Numeric."
+                );
+            }
+            _ => panic!("expected the strategy to be invalid"),
+        }
+    }
+}
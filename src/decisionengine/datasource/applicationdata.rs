@@ -1,13 +1,27 @@
+use chrono::NaiveDate;
 use decisionengine::datasource::DecisionDataInputNode;
 use decisionengine::datasource::DecisionDataRequestHandler;
 use decisionengine::datasource::DecisionDataset;
 use decisionengine::nodes::NodeResult;
+use decisionengine::operations::years_between;
+use decisionengine::types::NodeType;
+use rust_decimal::Decimal;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ApplicationDataV1 {
     first_name: String,
     last_name: String,
-    age: i32,
+    /// Derived from `date_of_birth` when not given.
+    #[serde(default)]
+    age: Option<i32>,
+    #[serde(default)]
+    date_of_birth: Option<NaiveDate>,
+    #[serde(default)]
+    address_since: Option<NaiveDate>,
+    #[serde(default)]
+    income: Option<Decimal>,
+    #[serde(default)]
+    loan_amount: Option<Decimal>,
 }
 
 impl ApplicationDataV1 {
@@ -23,44 +37,172 @@ impl ApplicationDataV1 {
         self.last_name.clone()
     }
 
-    pub fn age(&self) -> i32 {
+    pub fn age(&self) -> Option<i32> {
         self.age
     }
+
+    pub fn date_of_birth(&self) -> Option<NaiveDate> {
+        self.date_of_birth
+    }
+
+    pub fn address_since(&self) -> Option<NaiveDate> {
+        self.address_since
+    }
+
+    pub fn income(&self) -> Option<Decimal> {
+        self.income
+    }
+
+    pub fn loan_amount(&self) -> Option<Decimal> {
+        self.loan_amount
+    }
 }
 
 impl DecisionDataRequestHandler<ApplicationDataV1> for ApplicationDataV1 {
-    fn parse_node(path: &mut Vec<&str>) -> DecisionDataInputNode {
-        DecisionDataInputNode {
-            handler: match path.remove(0) {
-                "first_name" => Box::from(|decision_dataset: &mut DecisionDataset| {
+    fn parse_node(path: &mut Vec<&str>) -> Option<DecisionDataInputNode> {
+        Some(match path.remove(0) {
+            "first_name" => DecisionDataInputNode::new(
+                NodeType::Text,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
                     match decision_dataset.get_application_data_v1() {
                         Some(data) => NodeResult::Text(data.first_name()),
-                        _ => panic!(format!(
-                            "Decision data type {} not included in module but is accessed.",
-                            Self::decision_data_type()
-                        )),
+                        _ => NodeResult::Null,
                     }
                 }),
-                "last_name" => Box::from(|decision_dataset: &mut DecisionDataset| {
+            ),
+            "last_name" => DecisionDataInputNode::new(
+                NodeType::Text,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
                     match decision_dataset.get_application_data_v1() {
                         Some(data) => NodeResult::Text(data.last_name()),
-                        _ => {
-                            panic!("Decision data type {} not included in module but is accessed.")
-                        }
+                        _ => NodeResult::Null,
+                    }
+                }),
+            ),
+            "age" => DecisionDataInputNode::new(
+                NodeType::Numeric,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    let (age, date_of_birth) = match decision_dataset.get_application_data_v1() {
+                        Some(data) => (data.age(), data.date_of_birth()),
+                        None => return NodeResult::Null,
+                    };
+                    // The clock is only read when the age must be derived
+                    // from the date of birth.
+                    let age = age.or_else(|| {
+                        date_of_birth.map(|date_of_birth| {
+                            years_between(date_of_birth, decision_dataset.now().naive_utc().date())
+                        })
+                    });
+                    match age {
+                        Some(age) => NodeResult::Numeric(Decimal::from(age)),
+                        None => NodeResult::Null,
+                    }
+                }),
+            ),
+            "date_of_birth" => DecisionDataInputNode::new(
+                NodeType::Date,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    match decision_dataset.get_application_data_v1() {
+                        Some(data) => match data.date_of_birth() {
+                            Some(date_of_birth) => NodeResult::Date(date_of_birth),
+                            None => NodeResult::Null,
+                        },
+                        _ => NodeResult::Null,
+                    }
+                }),
+            ),
+            "address_since" => DecisionDataInputNode::new(
+                NodeType::Date,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    match decision_dataset.get_application_data_v1() {
+                        Some(data) => match data.address_since() {
+                            Some(address_since) => NodeResult::Date(address_since),
+                            None => NodeResult::Null,
+                        },
+                        _ => NodeResult::Null,
+                    }
+                }),
+            ),
+            "income" => DecisionDataInputNode::new(
+                NodeType::Numeric,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    match decision_dataset.get_application_data_v1() {
+                        Some(data) => match data.income() {
+                            Some(income) => NodeResult::Numeric(income),
+                            None => NodeResult::Null,
+                        },
+                        _ => NodeResult::Null,
                     }
                 }),
-                "age" => {
-                    Box::from(|decision_dataset: &mut DecisionDataset| {
-                        match decision_dataset.get_application_data_v1() {
-                            Some(data) => NodeResult::Numeric(data.age()),
-                            _ => panic!(
-                                "Decision data type {} not included in module but is accessed."
-                            ),
-                        }
-                    })
-                }
-                _ => panic!("Unknown query value for Experian v1.1"),
-            },
+            ),
+            "loan_amount" => DecisionDataInputNode::new(
+                NodeType::Numeric,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    match decision_dataset.get_application_data_v1() {
+                        Some(data) => match data.loan_amount() {
+                            Some(loan_amount) => NodeResult::Numeric(loan_amount),
+                            None => NodeResult::Null,
+                        },
+                        _ => NodeResult::Null,
+                    }
+                }),
+            ),
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use decisionengine::datasource::clock::Clock;
+    use decisionengine::nodes::NodeResult;
+    use decisionengine::testing::{dataset, eval_with};
+    use rust_decimal::Decimal;
+    use serde_json::Value;
+
+    fn age(application_data: Value) -> NodeResult {
+        eval_with(
+            json!({"type": "input", "value": "application_data_v1.age"}),
+            &mut dataset(application_data),
+        )
+    }
+
+    struct UnreadClock {}
+
+    impl Clock for UnreadClock {
+        fn now(&self) -> DateTime<Utc> {
+            panic!("the clock was read");
         }
     }
+
+    #[test]
+    fn age_is_taken_as_given() {
+        let result = age(json!({"age": 40, "date_of_birth": "1990-01-01"}));
+        assert_eq!(result, NodeResult::Numeric(Decimal::from(40)));
+    }
+
+    #[test]
+    fn given_age_does_not_read_the_clock() {
+        let mut input = dataset(json!({"age": 40, "date_of_birth": "1990-01-01"}))
+            .with_clock(Box::new(UnreadClock {}));
+        let result = eval_with(
+            json!({"type": "input", "value": "application_data_v1.age"}),
+            &mut input,
+        );
+        assert_eq!(result, NodeResult::Numeric(Decimal::from(40)));
+    }
+
+    #[test]
+    fn age_is_derived_from_date_of_birth() {
+        let result = age(json!({"age": null, "date_of_birth": "1990-08-17"}));
+        assert_eq!(result, NodeResult::Numeric(Decimal::from(28)));
+        let result = age(json!({"age": null, "date_of_birth": "1990-08-18"}));
+        assert_eq!(result, NodeResult::Numeric(Decimal::from(27)));
+    }
+
+    #[test]
+    fn age_is_null_without_date_of_birth() {
+        assert_eq!(age(json!({"age": null})), NodeResult::Null);
+    }
 }
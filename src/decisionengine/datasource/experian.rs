@@ -1,7 +1,11 @@
+use chrono::NaiveDate;
 use decisionengine::datasource::DecisionDataInputNode;
 use decisionengine::datasource::DecisionDataRequestHandler;
 use decisionengine::datasource::DecisionDataset;
 use decisionengine::nodes::NodeResult;
+use decisionengine::types::NodeType;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ExperianV1_0 {
@@ -11,7 +15,62 @@ pub struct ExperianV1_0 {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ExperianV1_1 {
     pub score: i32,
-    pub debt: i32,
+    pub debt: Decimal,
+    #[serde(default)]
+    pub accounts: Vec<ExperianAccount>,
+    #[serde(default)]
+    pub searches: Vec<ExperianSearch>,
+}
+
+/// A credit account on the applicant's file.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExperianAccount {
+    pub status: String,
+    pub balance: Decimal,
+    pub opened_on: NaiveDate,
+    #[serde(default)]
+    pub defaulted_on: Option<NaiveDate>,
+}
+
+impl ExperianAccount {
+    fn to_node_result(&self) -> NodeResult {
+        let mut fields = BTreeMap::new();
+        fields.insert(
+            String::from("status"),
+            NodeResult::Text(self.status.clone()),
+        );
+        fields.insert(String::from("balance"), NodeResult::Numeric(self.balance));
+        fields.insert(String::from("opened_on"), NodeResult::Date(self.opened_on));
+        fields.insert(
+            String::from("defaulted_on"),
+            self.defaulted_on
+                .map(NodeResult::Date)
+                .unwrap_or(NodeResult::Null),
+        );
+        NodeResult::Record(fields)
+    }
+}
+
+/// A credit search made against the applicant's file by a lender.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExperianSearch {
+    pub searched_on: NaiveDate,
+    pub search_type: String,
+}
+
+impl ExperianSearch {
+    fn to_node_result(&self) -> NodeResult {
+        let mut fields = BTreeMap::new();
+        fields.insert(
+            String::from("searched_on"),
+            NodeResult::Date(self.searched_on),
+        );
+        fields.insert(
+            String::from("search_type"),
+            NodeResult::Text(self.search_type.clone()),
+        );
+        NodeResult::Record(fields)
+    }
 }
 
 impl ExperianV1_0 {
@@ -21,22 +80,19 @@ impl ExperianV1_0 {
 }
 
 impl DecisionDataRequestHandler<ExperianV1_0> for ExperianV1_0 {
-    fn parse_node(path: &mut Vec<&str>) -> DecisionDataInputNode {
-        DecisionDataInputNode {
-            handler: match path.remove(0) {
-                "score" => {
-                    Box::from(|decision_dataset: &mut DecisionDataset| {
-                        match decision_dataset.get_experian_v1_0() {
-                            Some(ref data) => NodeResult::Numeric(data.score()),
-                            _ => panic!(
-                                "Decision data type {} not included in module but is accessed."
-                            ),
-                        }
-                    })
-                }
-                _ => panic!("Unknown query value for Experian v1.0"),
-            },
-        }
+    fn parse_node(path: &mut Vec<&str>) -> Option<DecisionDataInputNode> {
+        Some(match path.remove(0) {
+            "score" => DecisionDataInputNode::new(
+                NodeType::Numeric,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    match decision_dataset.get_experian_v1_0() {
+                        Some(ref data) => NodeResult::Numeric(Decimal::from(data.score())),
+                        _ => NodeResult::Null,
+                    }
+                }),
+            ),
+            _ => return None,
+        })
     }
 }
 
@@ -49,38 +105,69 @@ impl ExperianV1_1 {
         self.score
     }
 
-    pub fn debt(&self) -> i32 {
+    pub fn debt(&self) -> Decimal {
         self.debt
     }
+
+    pub fn accounts(&self) -> &Vec<ExperianAccount> {
+        &self.accounts
+    }
+
+    pub fn searches(&self) -> &Vec<ExperianSearch> {
+        &self.searches
+    }
 }
 
 impl DecisionDataRequestHandler<ExperianV1_1> for ExperianV1_1 {
-    fn parse_node(path: &mut Vec<&str>) -> DecisionDataInputNode {
-        DecisionDataInputNode {
-            handler: match path.remove(0) {
-                "score" => {
-                    Box::from(|decision_dataset: &mut DecisionDataset| {
-                        match decision_dataset.get_experian_v1_1() {
-                            Some(data) => NodeResult::Numeric(data.score()),
-                            _ => panic!(format!(
-                                "Decision data type {} not included in module but is accessed.",
-                                Self::decision_data_type()
-                            )),
-                        }
-                    })
-                }
-                "debt" => {
-                    Box::from(|decision_dataset: &mut DecisionDataset| {
-                        match decision_dataset.get_experian_v1_1() {
-                            Some(data) => NodeResult::Numeric(data.debt()),
-                            _ => panic!(
-                                "Decision data type {} not included in module but is accessed."
-                            ),
-                        }
-                    })
-                }
-                _ => panic!("Unknown query value for Experian v1.1"),
-            },
-        }
+    fn parse_node(path: &mut Vec<&str>) -> Option<DecisionDataInputNode> {
+        Some(match path.remove(0) {
+            "score" => DecisionDataInputNode::new(
+                NodeType::Numeric,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    match decision_dataset.get_experian_v1_1() {
+                        Some(data) => NodeResult::Numeric(Decimal::from(data.score())),
+                        _ => NodeResult::Null,
+                    }
+                }),
+            ),
+            "debt" => DecisionDataInputNode::new(
+                NodeType::Numeric,
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    match decision_dataset.get_experian_v1_1() {
+                        Some(data) => NodeResult::Numeric(data.debt()),
+                        _ => NodeResult::Null,
+                    }
+                }),
+            ),
+            "accounts" => DecisionDataInputNode::new(
+                NodeType::Array(Box::new(NodeType::Record)),
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    match decision_dataset.get_experian_v1_1() {
+                        Some(data) => NodeResult::Array(
+                            data.accounts()
+                                .iter()
+                                .map(ExperianAccount::to_node_result)
+                                .collect(),
+                        ),
+                        _ => NodeResult::Null,
+                    }
+                }),
+            ),
+            "searches" => DecisionDataInputNode::new(
+                NodeType::Array(Box::new(NodeType::Record)),
+                Box::from(|decision_dataset: &mut DecisionDataset| {
+                    match decision_dataset.get_experian_v1_1() {
+                        Some(data) => NodeResult::Array(
+                            data.searches()
+                                .iter()
+                                .map(ExperianSearch::to_node_result)
+                                .collect(),
+                        ),
+                        _ => NodeResult::Null,
+                    }
+                }),
+            ),
+            _ => return None,
+        })
     }
 }
@@ -1,6 +1,10 @@
+use chrono::NaiveDate;
 use decisionengine::datasource::applicationdata::ApplicationDataV1;
-use decisionengine::datasource::experian::{ExperianV1_0, ExperianV1_1};
+use decisionengine::datasource::experian::{
+    ExperianAccount, ExperianSearch, ExperianV1_0, ExperianV1_1,
+};
 use decisionengine::datasource::DecisionDataFetcher;
+use rust_decimal::Decimal;
 
 pub struct MockedExperianV1_1Fetcher {
     data: ExperianV1_1,
@@ -15,7 +19,25 @@ impl MockedExperianV1_1Fetcher {
         Self {
             data: ExperianV1_1 {
                 score: 1000,
-                debt: 0,
+                debt: Decimal::from(0),
+                accounts: vec![
+                    ExperianAccount {
+                        status: String::from("active"),
+                        balance: Decimal::from(1200),
+                        opened_on: NaiveDate::from_ymd(2015, 3, 1),
+                        defaulted_on: None,
+                    },
+                    ExperianAccount {
+                        status: String::from("default"),
+                        balance: Decimal::from(350),
+                        opened_on: NaiveDate::from_ymd(2012, 9, 14),
+                        defaulted_on: Some(NaiveDate::from_ymd(2016, 1, 20)),
+                    },
+                ],
+                searches: vec![ExperianSearch {
+                    searched_on: NaiveDate::from_ymd(2018, 6, 2),
+                    search_type: String::from("credit_card"),
+                }],
             },
         }
     }
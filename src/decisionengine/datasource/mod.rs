@@ -1,30 +1,67 @@
 extern crate serde;
 extern crate serde_json;
 
+use chrono::{DateTime, Utc};
 use decisionengine::datasource::applicationdata::ApplicationDataV1;
+use decisionengine::datasource::clock::{Clock, SystemClock};
 use decisionengine::datasource::experian::ExperianV1_0;
 use decisionengine::datasource::experian::ExperianV1_1;
 use decisionengine::datasource::mocks::decisiondatafetcher::{MockedExperianV1_0Fetcher,
                                                              MockedExperianV1_1Fetcher};
+use decisionengine::errors::DecisionEngineError;
 use decisionengine::nodes::EvalNode;
 use decisionengine::nodes::NodeResult;
+use decisionengine::results::Reason;
+use decisionengine::types::NodeType;
+use decisionengine::variables::{VariableTypes, Variables};
+use decisionengine::EvalResult;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 pub mod applicationdata;
+pub mod clock;
 pub mod experian;
 pub mod mocks;
 
 pub struct DecisionDataInputNode {
-    handler: Box<Fn(&mut DecisionDataset) -> NodeResult>,
+    path: String,
+    node_type: NodeType,
+    handler: Box<Fn(&mut DecisionDataset) -> NodeResult + Send + Sync>,
+}
+
+impl DecisionDataInputNode {
+    pub fn new(
+        node_type: NodeType,
+        handler: Box<Fn(&mut DecisionDataset) -> NodeResult + Send + Sync>,
+    ) -> Self {
+        DecisionDataInputNode {
+            path: String::new(),
+            node_type: node_type,
+            handler: handler,
+        }
+    }
 }
 
 impl EvalNode for DecisionDataInputNode {
-    fn eval(&mut self, decision_dataset: &mut DecisionDataset) -> NodeResult {
+    fn eval(&self, decision_dataset: &mut DecisionDataset) -> NodeResult {
         (self.handler)(decision_dataset)
     }
+
+    fn node_type(&self, _variables: &VariableTypes) -> Result<NodeType, String> {
+        Ok(self.node_type.clone())
+    }
+
+    fn serialize(&self) -> Value {
+        let mut node = Map::new();
+        node.insert(String::from("type"), Value::from("input"));
+        node.insert(String::from("value"), Value::from(self.path.as_str()));
+        Value::Object(node)
+    }
 }
 
 pub trait DecisionDataRequestHandler<T> {
-    fn parse_node(path_parts: &mut Vec<&str>) -> DecisionDataInputNode;
+    fn parse_node(path_parts: &mut Vec<&str>) -> Option<DecisionDataInputNode>;
 }
 
 pub struct DecisionDataset {
@@ -35,9 +72,26 @@ pub struct DecisionDataset {
 
     experian_v1_1: Option<ExperianV1_1>,
     experian_v1_1_fetcher: Box<DecisionDataFetcher<ApplicationDataV1, ExperianV1_1>>,
+
+    reasons: Vec<Reason>,
+
+    steps: usize,
+    step_budget: usize,
+
+    clock: Box<Clock>,
+    now: Option<DateTime<Utc>>,
+
+    elements: Vec<NodeResult>,
+
+    variables: Arc<Variables>,
+    variable_values: BTreeMap<String, NodeResult>,
 }
 
-trait DecisionDataFetcher<D, R> {
+/// Number of conditions a single decision may evaluate before it is
+/// aborted, unless configured otherwise.
+pub const DEFAULT_STEP_BUDGET: usize = 10000;
+
+trait DecisionDataFetcher<D, R>: Send {
     fn fetch(&self, data: &D) -> R;
 }
 
@@ -49,6 +103,14 @@ impl DecisionDataset {
             experian_v1_0_fetcher: Box::from(MockedExperianV1_0Fetcher::test()),
             experian_v1_1: None,
             experian_v1_1_fetcher: Box::from(MockedExperianV1_1Fetcher::test()),
+            reasons: Vec::new(),
+            steps: 0,
+            step_budget: DEFAULT_STEP_BUDGET,
+            clock: Box::new(SystemClock {}),
+            now: None,
+            elements: Vec::new(),
+            variables: Arc::new(Variables::empty()),
+            variable_values: BTreeMap::new(),
         }
     }
 
@@ -59,46 +121,186 @@ impl DecisionDataset {
             experian_v1_0_fetcher: Box::from(MockedExperianV1_0Fetcher::test()),
             experian_v1_1: None,
             experian_v1_1_fetcher: Box::from(MockedExperianV1_1Fetcher::test()),
+            reasons: Vec::new(),
+            steps: 0,
+            step_budget: DEFAULT_STEP_BUDGET,
+            clock: Box::new(SystemClock {}),
+            now: None,
+            elements: Vec::new(),
+            variables: Arc::new(Variables::empty()),
+            variable_values: BTreeMap::new(),
         }
     }
 
+    pub fn with_step_budget(mut self, step_budget: usize) -> Self {
+        self.step_budget = step_budget;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Box<Clock>) -> Self {
+        self.clock = clock;
+        self.now = None;
+        self
+    }
+
+    /// Current time according to the clock, read once so that every `now`
+    /// node in a decision sees the same instant.
+    pub fn now(&mut self) -> DateTime<Utc> {
+        match self.now {
+            Some(now) => now,
+            None => {
+                let now = self.clock.now();
+                self.now = Some(now);
+                now
+            }
+        }
+    }
+
+    /// Makes the variables of the strategy being evaluated available to its
+    /// conditions.
+    pub fn bind_variables(&mut self, variables: Arc<Variables>) {
+        self.variables = variables;
+    }
+
+    /// Value of a strategy variable, evaluated on first use.
+    pub fn variable(&mut self, name: &str) -> NodeResult {
+        if let Some(value) = self.variable_values.get(name) {
+            return value.clone();
+        }
+        let variables = self.variables.clone();
+        let value = variables.eval(name, self);
+        self.variable_values.insert(name.to_string(), value.clone());
+        value
+    }
+
+    /// Variables evaluated so far in this decision.
+    pub fn variable_values(&self) -> &BTreeMap<String, NodeResult> {
+        &self.variable_values
+    }
+
+    /// Array element bound by the innermost array operation being evaluated.
+    pub fn element(&self) -> Option<&NodeResult> {
+        self.elements.last()
+    }
+
+    pub fn push_element(&mut self, element: NodeResult) {
+        self.elements.push(element);
+    }
+
+    pub fn pop_element(&mut self) {
+        self.elements.pop();
+    }
+
+    /// Number of evaluation steps counted so far.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Winds the step count back to an earlier `steps()`, so that the steps
+    /// since are not counted against the budget.
+    pub fn reset_steps(&mut self, steps: usize) {
+        self.steps = steps;
+    }
+
+    /// Counts one evaluation step against the budget of this decision.
+    pub fn step(&mut self) -> Result<(), DecisionEngineError> {
+        self.steps += 1;
+        if self.steps > self.step_budget {
+            return Err(DecisionEngineError::StepBudgetExceeded(self.step_budget));
+        }
+        Ok(())
+    }
+
     pub fn get_application_data_v1(&self) -> Option<&ApplicationDataV1> {
         self.application_data_v1.as_ref()
     }
 
     pub fn get_experian_v1_0(&mut self) -> Option<&ExperianV1_0> {
         if self.experian_v1_0.is_none() {
-            self.experian_v1_0 = Some(
-                self.experian_v1_0_fetcher
-                    .fetch(self.application_data_v1.as_ref().unwrap()),
-            )
+            if let Some(application_data) = self.application_data_v1.as_ref() {
+                self.experian_v1_0 = Some(self.experian_v1_0_fetcher.fetch(application_data));
+            }
         }
         self.experian_v1_0.as_ref()
     }
 
     pub fn get_experian_v1_1(&mut self) -> Option<&ExperianV1_1> {
         if self.experian_v1_1.is_none() {
-            self.experian_v1_1 = Some(
-                self.experian_v1_1_fetcher
-                    .fetch(self.application_data_v1.as_ref().unwrap()),
-            )
+            if let Some(application_data) = self.application_data_v1.as_ref() {
+                self.experian_v1_1 = Some(self.experian_v1_1_fetcher.fetch(application_data));
+            }
         }
         self.experian_v1_1.as_ref()
     }
+
+    /// Bureau data fetched while evaluating the decision, by data source.
+    /// Sources the strategy never read are left out.
+    pub fn bureau_data(&self) -> Value {
+        let mut bureau_data = Map::new();
+        if let Some(ref experian_v1_0) = self.experian_v1_0 {
+            bureau_data.insert(
+                String::from("experian_v1_0"),
+                serde_json::to_value(experian_v1_0).unwrap_or(Value::Null),
+            );
+        }
+        if let Some(ref experian_v1_1) = self.experian_v1_1 {
+            bureau_data.insert(
+                String::from("experian_v1_1"),
+                serde_json::to_value(experian_v1_1).unwrap_or(Value::Null),
+            );
+        }
+        Value::Object(bureau_data)
+    }
+
+    pub fn reasons(&self) -> &Vec<Reason> {
+        &self.reasons
+    }
+
+    pub fn add_reason(&mut self, reason: Reason) {
+        self.reasons.push(reason);
+    }
+
+    /// Drops the reasons recorded from index `from` onwards.
+    pub fn truncate_reasons(&mut self, from: usize) {
+        self.reasons.truncate(from);
+    }
+
+    /// Drops the reasons recorded from index `from` onwards that did not
+    /// lead to `result`.
+    pub fn retain_reasons(&mut self, from: usize, result: &EvalResult) {
+        let retained: Vec<Reason> = self
+            .reasons
+            .split_off(from)
+            .into_iter()
+            .filter(|r| &r.result == result)
+            .collect();
+        self.reasons.extend(retained);
+    }
 }
 
-pub fn deserialize_input_node(path: &str) -> (Box<EvalNode>, bool) {
+pub fn parse_input_node(path: &str) -> Option<DecisionDataInputNode> {
     let mut path_parts: Vec<&str> = path.split(".").collect();
-    if path_parts.len() < 2 {
-        panic!(format!("Input {} has length < 2", path))
-    }
-    (
-        Box::from(match path_parts.remove(0) {
-            "experian_v1_0" => ExperianV1_0::parse_node(&mut path_parts),
-            "experian_v1_1" => ExperianV1_1::parse_node(&mut path_parts),
-            "application_data_v1" => ApplicationDataV1::parse_node(&mut path_parts),
-            _ => panic!(format!("Cannot parse input {}", path)),
-        }),
-        false,
-    )
+    if path_parts.len() != 2 {
+        return None;
+    }
+    let node = match path_parts.remove(0) {
+        "experian_v1_0" => ExperianV1_0::parse_node(&mut path_parts),
+        "experian_v1_1" => ExperianV1_1::parse_node(&mut path_parts),
+        "application_data_v1" => ApplicationDataV1::parse_node(&mut path_parts),
+        _ => None,
+    };
+    node.map(|mut node| {
+        node.path = path.to_string();
+        node
+    })
+}
+
+pub fn deserialize_input_node(path: &str) -> Result<(Box<EvalNode>, bool), DecisionEngineError> {
+    match parse_input_node(path) {
+        Some(node) => Ok((Box::from(node), false)),
+        None => Err(DecisionEngineError::MalformedStrategy(format!(
+            "Cannot parse input {}",
+            path
+        ))),
+    }
 }
@@ -0,0 +1,111 @@
+extern crate serde_json;
+
+use chrono::{DateTime, Utc};
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::schema::decision;
+use diesel;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde_json::Value;
+
+/// Stored decision, as recorded by `NewDecision`.
+#[derive(Queryable, Serialize)]
+pub struct Decision {
+    pub decision_id: i32,
+    pub decision_strategy_id: i32,
+    pub application_data: Value,
+    pub result: Option<Value>,
+    pub decided_at: DateTime<Utc>,
+    pub decision_strategy_hash: String,
+    pub bureau_data: Value,
+    pub outcome: String,
+    pub details: Value,
+}
+
+/// Summary of a stored decision for listings, without its input or trace.
+#[derive(Queryable, Serialize)]
+pub struct DecisionSummary {
+    pub decision_id: i32,
+    pub decision_strategy_id: i32,
+    pub decided_at: DateTime<Utc>,
+    pub outcome: String,
+}
+
+/// Criteria for listing stored decisions. Unset criteria match every
+/// decision; `decided_from` is inclusive and `decided_to` exclusive.
+pub struct DecisionFilter {
+    pub decision_strategy_id: Option<i32>,
+    pub outcome: Option<String>,
+    pub decided_from: Option<DateTime<Utc>>,
+    pub decided_to: Option<DateTime<Utc>>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Decision {
+    pub fn from_id(id: i32, connection: &PgConnection) -> Result<Self, DecisionEngineError> {
+        decision::table
+            .find(id)
+            .first::<Decision>(connection)
+            .optional()?
+            .ok_or(DecisionEngineError::DecisionNotFound(id))
+    }
+
+    /// Decisions matching the filter, most recent first.
+    pub fn list(
+        filter: &DecisionFilter,
+        connection: &PgConnection,
+    ) -> Result<Vec<DecisionSummary>, DecisionEngineError> {
+        let mut query = decision::table
+            .select((
+                decision::decision_id,
+                decision::decision_strategy_id,
+                decision::decided_at,
+                decision::outcome,
+            ))
+            .into_boxed();
+        if let Some(decision_strategy_id) = filter.decision_strategy_id {
+            query = query.filter(decision::decision_strategy_id.eq(decision_strategy_id));
+        }
+        if let Some(ref outcome) = filter.outcome {
+            query = query.filter(decision::outcome.eq(outcome));
+        }
+        if let Some(decided_from) = filter.decided_from {
+            query = query.filter(decision::decided_at.ge(decided_from));
+        }
+        if let Some(decided_to) = filter.decided_to {
+            query = query.filter(decision::decided_at.lt(decided_to));
+        }
+        Ok(query
+            .order((decision::decided_at.desc(), decision::decision_id.desc()))
+            .limit(filter.limit)
+            .offset(filter.offset)
+            .load(connection)?)
+    }
+}
+
+/// Audit record of a single decision, holding everything needed to
+/// reproduce it: the strategy and its content hash, the application and
+/// bureau data it was made on and the time it was made at.
+#[derive(Insertable)]
+#[table_name = "decision"]
+pub struct NewDecision {
+    pub decision_strategy_id: i32,
+    pub decision_strategy_hash: String,
+    pub decided_at: DateTime<Utc>,
+    pub application_data: Value,
+    pub bureau_data: Value,
+    pub outcome: String,
+    pub result: Option<Value>,
+    pub details: Value,
+}
+
+impl NewDecision {
+    /// Stores the decision, returning its `decision_id`.
+    pub fn insert(&self, connection: &PgConnection) -> Result<i32, DecisionEngineError> {
+        Ok(diesel::insert_into(decision::table)
+            .values(self)
+            .returning(decision::decision_id)
+            .get_result(connection)?)
+    }
+}
@@ -1,52 +1,87 @@
-use decisionengine::modules::{ModuleChildren, PassAllModule};
-use decisionengine::rules::{Condition, Rule};
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::modules::{ModuleChildren, PassAllModule, Precedence};
+use decisionengine::rules::{deserialize_rule, Rule};
 use serde_json::Value;
-use std::collections::HashMap;
+
+pub fn get_str<'a>(value: &'a Value, key: &str) -> Result<&'a str, DecisionEngineError> {
+    value[key].as_str().ok_or_else(|| {
+        DecisionEngineError::MalformedStrategy(format!("Expected string field \"{}\".", key))
+    })
+}
+
+pub fn get_array<'a>(value: &'a Value, key: &str) -> Result<&'a Vec<Value>, DecisionEngineError> {
+    value[key].as_array().ok_or_else(|| {
+        DecisionEngineError::MalformedStrategy(format!("Expected array field \"{}\".", key))
+    })
+}
+
+pub fn get_i64(value: &Value, key: &str) -> Result<i64, DecisionEngineError> {
+    value[key].as_i64().ok_or_else(|| {
+        DecisionEngineError::MalformedStrategy(format!("Expected integer field \"{}\".", key))
+    })
+}
+
+pub fn get_i32(value: &Value, key: &str) -> Result<i32, DecisionEngineError> {
+    value[key]
+        .as_i64()
+        .filter(|n| *n >= i64::from(i32::min_value()) && *n <= i64::from(i32::max_value()))
+        .map(|n| n as i32)
+        .ok_or_else(|| {
+            DecisionEngineError::MalformedStrategy(format!(
+                "Expected 32-bit integer field \"{}\".",
+                key
+            ))
+        })
+}
+
+pub fn get_u64(value: &Value, key: &str) -> Result<u64, DecisionEngineError> {
+    value[key].as_u64().ok_or_else(|| {
+        DecisionEngineError::MalformedStrategy(format!(
+            "Expected non-negative integer field \"{}\".",
+            key
+        ))
+    })
+}
 
 trait Deserializer {
-    fn deserialize_rule(&self, value: &Value) -> Rule;
-    fn deserialize_module(&self, value: &Value) -> PassAllModule;
+    fn deserialize_rule(&self, value: &Value) -> Result<Rule, DecisionEngineError>;
+    fn deserialize_module(&self, value: &Value) -> Result<PassAllModule, DecisionEngineError>;
 }
 
 pub struct DefaultDeserializer {}
 
 impl Deserializer for DefaultDeserializer {
-    fn deserialize_module(&self, value: &Value) -> PassAllModule {
-        let children = value["children"]
-            .as_array()
-            .unwrap()
-            .into_iter()
-            .map(|x| match x["type"].as_str().unwrap() {
-                "rule" => ModuleChildren::Rule(self.deserialize_rule(x)),
-                "module" => ModuleChildren::PassAllModule(self.deserialize_module(x)),
-                _ => panic!("Unknown module children type"),
-            })
-            .collect();
-
-        let module = match value["module_type"].as_str().unwrap() {
+    fn deserialize_module(&self, value: &Value) -> Result<PassAllModule, DecisionEngineError> {
+        let mut children = Vec::new();
+        for x in get_array(value, "children")? {
+            children.push(match get_str(x, "type")? {
+                "rule" => ModuleChildren::Rule(self.deserialize_rule(x)?),
+                "module" => ModuleChildren::PassAllModule(self.deserialize_module(x)?),
+                _ => {
+                    return Err(DecisionEngineError::MalformedStrategy(String::from(
+                        "Unknown module children type",
+                    )))
+                }
+            });
+        }
+
+        let module = match get_str(value, "module_type")? {
             "all" => PassAllModule {
-                module_name: value["module_name"].as_str().unwrap().to_string(),
+                module_name: get_str(value, "module_name")?.to_string(),
+                precedence: Precedence::default(),
                 children: children,
             },
-            _ => panic!(format!(
-                "Unknown module_type: {}",
-                value["module_type"].as_str().unwrap()
-            )),
+            module_type => {
+                return Err(DecisionEngineError::MalformedStrategy(format!(
+                    "Unknown module_type: {}",
+                    module_type
+                )))
+            }
         };
-        module
+        Ok(module)
     }
 
-    fn deserialize_rule(&self, value: &Value) -> Rule {
-        let mut conditions = HashMap::new();
-        for condition in value["conditions"].as_array().unwrap() {
-            let r = Condition::deserialize(condition);
-            conditions.insert(r.condition_id, r);
-        }
-
-        Rule {
-            rule_name: value["rule_name"].as_str().unwrap().to_string(),
-            rule_id: value["rule_id"].as_i64().unwrap() as i32,
-            conditions: conditions,
-        }
+    fn deserialize_rule(&self, value: &Value) -> Result<Rule, DecisionEngineError> {
+        deserialize_rule(value)
     }
 }
@@ -0,0 +1,167 @@
+extern crate diesel;
+extern crate serde_json;
+
+use decisionengine::validation::ValidationError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone)]
+pub enum DecisionEngineError {
+    /// The request or input dataset could not be parsed.
+    BadRequest(String),
+    /// No decision strategy exists with the given id.
+    StrategyNotFound(i32),
+    /// The strategy has been retired and no longer makes decisions.
+    StrategyRetired(i32),
+    /// No decision exists with the given id.
+    DecisionNotFound(i32),
+    /// The strategy failed validation.
+    InvalidStrategy(Vec<ValidationError>),
+    /// The strategy could not be deserialized.
+    MalformedStrategy(String),
+    /// The strategy failed while being evaluated against a dataset.
+    Evaluation(String),
+    /// The decision evaluated more conditions than its step budget allows.
+    StepBudgetExceeded(usize),
+    /// The environment the engine was started in is invalid.
+    Config(String),
+    Database(String),
+    Io(String),
+}
+
+impl fmt::Display for DecisionEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecisionEngineError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
+            DecisionEngineError::StrategyNotFound(id) => {
+                write!(f, "Decision strategy {} not found.", id)
+            }
+            DecisionEngineError::StrategyRetired(id) => {
+                write!(f, "Decision strategy {} has been retired.", id)
+            }
+            DecisionEngineError::DecisionNotFound(id) => write!(f, "Decision {} not found.", id),
+            DecisionEngineError::InvalidStrategy(errors) => {
+                write!(f, "Invalid decision strategy:")?;
+                for error in errors {
+                    write!(f, "\n{}: {}", error.pointer, error.message)?;
+                }
+                Ok(())
+            }
+            DecisionEngineError::MalformedStrategy(msg) => {
+                write!(f, "Malformed decision strategy: {}", msg)
+            }
+            DecisionEngineError::Evaluation(msg) => write!(f, "Evaluation failed: {}", msg),
+            DecisionEngineError::StepBudgetExceeded(budget) => write!(
+                f,
+                "Evaluation failed: step budget of {} conditions exceeded.",
+                budget
+            ),
+            DecisionEngineError::Config(msg) => write!(f, "Configuration error: {}", msg),
+            DecisionEngineError::Database(msg) => write!(f, "Database error: {}", msg),
+            DecisionEngineError::Io(msg) => write!(f, "IO error: {}", msg),
+        }
+    }
+}
+
+impl Error for DecisionEngineError {}
+
+impl From<diesel::result::Error> for DecisionEngineError {
+    fn from(error: diesel::result::Error) -> Self {
+        DecisionEngineError::Database(error.to_string())
+    }
+}
+
+impl From<diesel::ConnectionError> for DecisionEngineError {
+    fn from(error: diesel::ConnectionError) -> Self {
+        DecisionEngineError::Database(error.to_string())
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for DecisionEngineError {
+    fn from(error: diesel::r2d2::PoolError) -> Self {
+        DecisionEngineError::Database(error.to_string())
+    }
+}
+
+impl From<io::Error> for DecisionEngineError {
+    fn from(error: io::Error) -> Self {
+        DecisionEngineError::Io(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decisionengine::testing::{dataset, rule_strategy};
+    use decisionengine::{deserialize_strategy, Evaluatable};
+    use serde_json::Value;
+
+    #[test]
+    fn malformed_strategies_are_errors() {
+        let mut unknown_module_type = rule_strategy(Value::from("true"));
+        unknown_module_type["module_type"] = json!("most");
+        let mut missing_conditions = rule_strategy(Value::from("true"));
+        missing_conditions["children"][0]["conditions"] = Value::Null;
+        let strategies = vec![
+            json!({}),
+            json!({"type": "module", "module_type": "all", "module_name": "Test"}),
+            unknown_module_type,
+            missing_conditions,
+            rule_strategy(json!({"type": "op", "op": "frobnicate", "value": true})),
+            rule_strategy(json!({"type": "mystery"})),
+        ];
+        for strategy in &strategies {
+            match deserialize_strategy(strategy) {
+                Err(DecisionEngineError::MalformedStrategy(_)) => {}
+                _ => panic!("expected {} to be malformed", strategy),
+            }
+        }
+    }
+
+    #[test]
+    fn evaluation_failures_are_errors() {
+        let strategy = deserialize_strategy(&rule_strategy(Value::from(
+            "1 / (application_data_v1.age - 30) > 0",
+        )))
+        .unwrap();
+        match strategy.eval(&mut dataset(json!({}))) {
+            Err(error @ DecisionEngineError::Evaluation(_)) => {
+                assert_eq!(error.to_string(), "Evaluation failed: Division by zero.")
+            }
+            _ => panic!("expected an evaluation error"),
+        }
+    }
+
+    #[test]
+    fn errors_describe_themselves() {
+        let error = DecisionEngineError::InvalidStrategy(vec![
+            ValidationError {
+                pointer: String::from("/type"),
+                message: String::from("Decision strategy must be a module."),
+            },
+            ValidationError {
+                pointer: String::from("/children"),
+                message: String::from("Expected array field \"children\"."),
+            },
+        ]);
+        assert_eq!(
+            error.to_string(),
+            "Invalid decision strategy:\n/type: Decision strategy must be a module.\n/children: Expected array field \"children\"."
+        );
+        assert_eq!(
+            DecisionEngineError::StrategyNotFound(7).to_string(),
+            "Decision strategy 7 not found."
+        );
+    }
+
+    #[test]
+    fn library_errors_convert() {
+        let error: DecisionEngineError = io::Error::new(io::ErrorKind::NotFound, "gone").into();
+        assert_eq!(error.to_string(), "IO error: gone");
+        match diesel::result::Error::NotFound.into() {
+            DecisionEngineError::Database(_) => {}
+            _ => panic!("expected a database error"),
+        }
+    }
+}
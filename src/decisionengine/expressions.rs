@@ -0,0 +1,597 @@
+extern crate serde_json;
+
+use decisionengine::nodes::{binary_operation, ternary_operation, unary_operation};
+use serde_json::{Map, Number, Value};
+use std::str::FromStr;
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(String),
+    Text(String),
+    Identifier(String),
+    Symbol(&'static str),
+    End,
+}
+
+const SYMBOLS: [&str; 19] = [
+    "&&", "||", "==", "!=", ">=", "<=", ">", "<", "+", "-", "*", "/", "%", "!", "(", ")", "[", "]",
+    ",",
+];
+
+/// Binary operators from lowest to highest precedence. All are left
+/// associative.
+const PRECEDENCE: [&[&str]; 6] = [
+    &["||"],
+    &["&&"],
+    &["==", "!="],
+    &[">=", "<=", ">", "<"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Deepest nesting of parentheses, array literals, function arguments, prefix
+/// operators and chained binary operators accepted, so that parsing cannot exhaust the stack. Each
+/// level is at most two levels of node JSON, keeping serialized expressions
+/// within serde_json's recursion limit of 128.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+fn error_at(position: &Position, message: &str) -> String {
+    format!(
+        "Expression error at line {}, column {}: {}",
+        position.line, position.column, message
+    )
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, Position)>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut column = 1;
+    while i < chars.len() {
+        let c = chars[i];
+        let position = Position {
+            line: line,
+            column: column,
+        };
+        let start = i;
+        if c == '\n' {
+            i += 1;
+            line += 1;
+            column = 1;
+            continue;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push((Token::Number(number), position));
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                i += 1;
+            }
+            let identifier: String = chars[start..i].iter().collect();
+            tokens.push((Token::Identifier(identifier), position));
+        } else if c == '"' {
+            i += 1;
+            let mut text = String::new();
+            loop {
+                match chars.get(i) {
+                    None | Some(&'\n') => {
+                        return Err(error_at(&position, "unterminated string literal."))
+                    }
+                    Some(&'"') => break,
+                    Some(&'\\') => {
+                        text.push(match chars.get(i + 1) {
+                            Some(&'n') => '\n',
+                            Some(&'t') => '\t',
+                            Some(&'"') => '"',
+                            Some(&'\\') => '\\',
+                            _ => {
+                                return Err(error_at(
+                                    &Position {
+                                        line: line,
+                                        column: column + i - start,
+                                    },
+                                    "invalid escape sequence.",
+                                ))
+                            }
+                        });
+                        i += 2;
+                    }
+                    Some(&c) => {
+                        text.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push((Token::Text(text), position));
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                Some(symbol) => {
+                    i += symbol.len();
+                    tokens.push((Token::Symbol(symbol), position));
+                }
+                None => return Err(error_at(&position, &format!("unexpected character {}.", c))),
+            }
+        }
+        column += i - start;
+    }
+    tokens.push((
+        Token::End,
+        Position {
+            line: line,
+            column: column,
+        },
+    ));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, Position)>,
+    next: usize,
+    depth: usize,
+}
+
+fn node(node_type: &str) -> Map<String, Value> {
+    let mut node = Map::new();
+    node.insert(String::from("type"), Value::from(node_type));
+    node
+}
+
+fn op_node(op: &str) -> Map<String, Value> {
+    let mut node = node("op");
+    node.insert(String::from("op"), Value::from(op));
+    node
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn position(&self) -> &Position {
+        &self.tokens[self.next].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].0.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: &str) -> String {
+        error_at(self.position(), message)
+    }
+
+    fn unexpected(&self) -> String {
+        self.error(&match self.peek() {
+            Token::Number(n) => format!("unexpected number {}.", n),
+            Token::Text(s) => format!("unexpected string \"{}\".", s),
+            Token::Identifier(i) => format!("unexpected {}.", i),
+            Token::Symbol(s) => format!("unexpected {}.", s),
+            Token::End => String::from("unexpected end of expression."),
+        })
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        if *self.peek() == Token::Symbol(symbol) {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}.", symbol)))
+        }
+    }
+
+    /// Runs `parse` one level of nesting deeper, failing past `MAX_DEPTH`.
+    fn nested<F>(&mut self, parse: F) -> Result<Value, String>
+    where
+        F: FnOnce(&mut Parser) -> Result<Value, String>,
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("expression is nested too deeply."));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Value, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let depth = self.depth;
+        let value = self.chain(level);
+        self.depth = depth;
+        value
+    }
+
+    /// Operators of one precedence level associate to the left, so each
+    /// further operand nests the chain so far one level deeper.
+    fn chain(&mut self, level: usize) -> Result<Value, String> {
+        let mut lvalue = self.binary(level + 1)?;
+        loop {
+            let op = match self.peek() {
+                &Token::Symbol(symbol) if PRECEDENCE[level].contains(&symbol) => symbol,
+                _ => return Ok(lvalue),
+            };
+            if self.depth == MAX_DEPTH {
+                return Err(self.error("expression is nested too deeply."));
+            }
+            self.depth += 1;
+            self.advance();
+            let rvalue = self.binary(level + 1)?;
+            let mut node = op_node(op);
+            node.insert(String::from("lvalue"), lvalue);
+            node.insert(String::from("rvalue"), rvalue);
+            lvalue = Value::Object(node);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        let op = match self.peek() {
+            Token::Symbol("!") => "!",
+            Token::Symbol("-") => "neg",
+            _ => return self.primary(),
+        };
+        self.advance();
+        let mut node = op_node(op);
+        node.insert(String::from("value"), self.nested(|parser| parser.unary())?);
+        Ok(Value::Object(node))
+    }
+
+    fn primary(&mut self) -> Result<Value, String> {
+        match self.peek().clone() {
+            Token::Number(n) => {
+                let number = Number::from_str(&n).map_err(|_| self.error("invalid number."))?;
+                self.advance();
+                Ok(constant(Value::Number(number)))
+            }
+            Token::Text(s) => {
+                self.advance();
+                Ok(constant(Value::String(s)))
+            }
+            Token::Symbol("(") => {
+                self.advance();
+                let value = self.nested(|parser| parser.binary(0))?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Token::Symbol("[") => self.nested(|parser| parser.array()),
+            Token::Identifier(identifier) => {
+                let position = self.position().clone();
+                self.advance();
+                if *self.peek() == Token::Symbol("(") {
+                    self.call(&identifier, position)
+                } else {
+                    Ok(reference(&identifier))
+                }
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// Array literals, which like constant arrays may only hold literals.
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect("[")?;
+        let mut values = Vec::new();
+        while *self.peek() != Token::Symbol("]") {
+            let value = match self.primary()? {
+                Value::Object(ref node) if node["type"] == "constant" => node["value"].clone(),
+                _ => return Err(self.error("array literals may only contain literals.")),
+            };
+            values.push(value);
+            if *self.peek() != Token::Symbol("]") {
+                self.expect(",")?;
+            }
+        }
+        self.advance();
+        Ok(constant(Value::Array(values)))
+    }
+
+    /// Function calls, with `position` that of the function name.
+    fn call(&mut self, function: &str, position: Position) -> Result<Value, String> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        while *self.peek() != Token::Symbol(")") {
+            args.push(self.nested(|parser| parser.binary(0))?);
+            if *self.peek() != Token::Symbol(")") {
+                self.expect(",")?;
+            }
+        }
+        self.advance();
+
+        let arity = args.len();
+        match (function, arity) {
+            ("now", 0) => return Ok(Value::Object(node("now"))),
+            ("date", 1) | ("datetime", 1) if args[0]["type"] == "constant" => {
+                if args[0]["value"].is_string() {
+                    let mut node = node("constant");
+                    node.insert(String::from("value"), args[0]["value"].clone());
+                    node.insert(String::from("value_type"), Value::from(function));
+                    return Ok(Value::Object(node));
+                }
+            }
+            _ => {}
+        }
+
+        let mut node = op_node(function);
+        let known = match arity {
+            1 if unary_operation(function).is_some() => {
+                node.insert(String::from("value"), args.remove(0));
+                true
+            }
+            2 if binary_operation(function).is_some() => {
+                node.insert(String::from("rvalue"), args.remove(1));
+                node.insert(String::from("lvalue"), args.remove(0));
+                true
+            }
+            3 if ternary_operation(function).is_some() => {
+                node.insert(String::from("args"), Value::Array(args));
+                true
+            }
+            _ => false,
+        };
+        if known {
+            Ok(Value::Object(node))
+        } else {
+            Err(error_at(
+                &position,
+                &format!("unknown function {} taking {} arguments.", function, arity),
+            ))
+        }
+    }
+}
+
+fn constant(value: Value) -> Value {
+    let mut node = node("constant");
+    node.insert(String::from("value"), value);
+    Value::Object(node)
+}
+
+/// Identifiers are inputs if they are a dotted path such as
+/// `experian_v1_1.score`, the current array element if they are `element`,
+/// optionally followed by a field name, and strategy variables otherwise,
+/// apart from the literals `true`, `false` and `null`.
+fn reference(identifier: &str) -> Value {
+    match identifier {
+        "true" => return constant(Value::Bool(true)),
+        "false" => return constant(Value::Bool(false)),
+        "null" => return constant(Value::Null),
+        _ => {}
+    }
+    let mut parts = identifier.splitn(2, '.');
+    let name = parts.next().unwrap_or_default();
+    let node = match (name, parts.next()) {
+        ("element", None) => node("element"),
+        ("element", Some(field)) => {
+            let mut node = node("element");
+            node.insert(String::from("value"), Value::from(field));
+            node
+        }
+        (_, None) => {
+            let mut node = node("variable");
+            node.insert(String::from("value"), Value::from(name));
+            node
+        }
+        (_, Some(_)) => {
+            let mut node = node("input");
+            node.insert(String::from("value"), Value::from(identifier));
+            node
+        }
+    };
+    Value::Object(node)
+}
+
+/// Parses the textual form of an expression, such as
+/// `experian_v1_1.score >= 750 && application_data_v1.age < 75`, into the
+/// JSON node form read by `deserialize_node`.
+///
+/// Operators have the usual precedence, from `||` binding loosest through
+/// `&&`, equality, comparison, `+`/`-` and `*`/`/`/`%` to the prefix `!` and
+/// `-`. Operations without an operator are called as functions, for example
+/// `lower(application_data_v1.first_name)` or `any(experian_v1_1.accounts,
+/// element.status == "default")`, and `date("2018-01-01")` and
+/// `datetime(...)` read date and datetime literals.
+pub fn parse_expression(source: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        next: 0,
+        depth: 0,
+    };
+    let value = parser.binary(0)?;
+    if *parser.peek() != Token::End {
+        return Err(parser.unexpected());
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decisionengine::nodes::NodeResult;
+    use decisionengine::testing::eval;
+
+    fn input(path: &str) -> Value {
+        json!({"type": "input", "value": path})
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        assert_eq!(
+            parse_expression("1 + 2 * 3 >= 7 || !false && x").unwrap(),
+            json!({
+                "type": "op", "op": "||",
+                "lvalue": {
+                    "type": "op", "op": ">=",
+                    "lvalue": {
+                        "type": "op", "op": "+",
+                        "lvalue": {"type": "constant", "value": 1},
+                        "rvalue": {
+                            "type": "op", "op": "*",
+                            "lvalue": {"type": "constant", "value": 2},
+                            "rvalue": {"type": "constant", "value": 3},
+                        },
+                    },
+                    "rvalue": {"type": "constant", "value": 7},
+                },
+                "rvalue": {
+                    "type": "op", "op": "&&",
+                    "lvalue": {
+                        "type": "op", "op": "!",
+                        "value": {"type": "constant", "value": false},
+                    },
+                    "rvalue": {"type": "variable", "value": "x"},
+                },
+            })
+        );
+        assert_eq!(eval(Value::from("(1 + 2) * 3")), eval(Value::from("9")));
+        assert_eq!(eval(Value::from("10 - 4 - 3")), eval(Value::from("3")));
+    }
+
+    #[test]
+    fn literals_and_references() {
+        assert_eq!(
+            parse_expression("[1, \"a\", null]").unwrap(),
+            json!({"type": "constant", "value": [1, "a", null]})
+        );
+        assert_eq!(
+            parse_expression("date(\"2018-01-01\")").unwrap(),
+            json!({"type": "constant", "value": "2018-01-01", "value_type": "date"})
+        );
+        assert_eq!(
+            parse_expression("-experian_v1_1.score").unwrap(),
+            json!({"type": "op", "op": "neg", "value": input("experian_v1_1.score")})
+        );
+        assert_eq!(
+            parse_expression("element.status").unwrap(),
+            json!({"type": "element", "value": "status"})
+        );
+        assert_eq!(parse_expression("now()").unwrap(), json!({"type": "now"}));
+    }
+
+    #[test]
+    fn functions_map_to_operations() {
+        assert_eq!(
+            parse_expression("lower(application_data_v1.first_name)").unwrap(),
+            json!({
+                "type": "op", "op": "lower",
+                "value": input("application_data_v1.first_name"),
+            })
+        );
+        assert_eq!(
+            parse_expression("coalesce(a, 0)").unwrap(),
+            json!({
+                "type": "op", "op": "coalesce",
+                "lvalue": {"type": "variable", "value": "a"},
+                "rvalue": {"type": "constant", "value": 0},
+            })
+        );
+    }
+
+    #[test]
+    fn errors_give_their_position() {
+        assert_eq!(
+            parse_expression("1 +\n  * 2").unwrap_err(),
+            "Expression error at line 2, column 3: unexpected *."
+        );
+        assert_eq!(
+            parse_expression("(1 + 2").unwrap_err(),
+            "Expression error at line 1, column 7: expected )."
+        );
+        assert_eq!(
+            parse_expression("x >= 1 && frobnicate(x)").unwrap_err(),
+            "Expression error at line 1, column 11: unknown function frobnicate taking 1 arguments."
+        );
+        assert_eq!(
+            parse_expression("x >= 1 && frobnicate (x)").unwrap_err(),
+            "Expression error at line 1, column 11: unknown function frobnicate taking 1 arguments."
+        );
+        assert_eq!(
+            parse_expression("frobnicate\n(x)").unwrap_err(),
+            "Expression error at line 1, column 1: unknown function frobnicate taking 1 arguments."
+        );
+        assert_eq!(
+            parse_expression("[1, x]").unwrap_err(),
+            "Expression error at line 1, column 6: array literals may only contain literals."
+        );
+    }
+
+    #[test]
+    fn deep_parentheses_are_an_error() {
+        let source = format!("{}1", "(".repeat(50000));
+        let error = parse_expression(&source).unwrap_err();
+        assert!(error.contains("nested too deeply"), "{}", error);
+    }
+
+    #[test]
+    fn deep_prefix_operators_are_an_error() {
+        let source = format!("{}true", "!".repeat(200000));
+        let error = parse_expression(&source).unwrap_err();
+        assert!(error.contains("nested too deeply"), "{}", error);
+    }
+
+    #[test]
+    fn long_operator_chains_are_an_error() {
+        let source = format!("1{}", "+1".repeat(200000));
+        let error = parse_expression(&source).unwrap_err();
+        assert!(error.contains("nested too deeply"), "{}", error);
+    }
+
+    #[test]
+    fn operator_chains_up_to_the_limit_round_trip() {
+        let source = format!("1{}", "+1".repeat(MAX_DEPTH));
+        let value = parse_expression(&source).unwrap();
+        let serialized = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&serialized).unwrap(), value);
+        assert_eq!(
+            eval(Value::from(source)),
+            eval(Value::from((MAX_DEPTH + 1).to_string()))
+        );
+        let source = format!("1{}", "+1".repeat(MAX_DEPTH + 1));
+        assert!(parse_expression(&source).is_err());
+    }
+
+    #[test]
+    fn deep_array_literals_are_an_error() {
+        let source = "[".repeat(50000);
+        let error = parse_expression(&source).unwrap_err();
+        assert!(error.contains("nested too deeply"), "{}", error);
+    }
+
+    #[test]
+    fn nesting_up_to_the_limit_parses() {
+        let source = format!("{}1{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert_eq!(
+            parse_expression(&source).unwrap(),
+            json!({"type": "constant", "value": 1})
+        );
+        let source = format!(
+            "{}1{}",
+            "(".repeat(MAX_DEPTH + 1),
+            ")".repeat(MAX_DEPTH + 1)
+        );
+        assert!(parse_expression(&source).is_err());
+    }
+
+    #[test]
+    fn nesting_up_to_the_limit_evaluates() {
+        let source = format!("{}true", "!".repeat(MAX_DEPTH));
+        assert_eq!(eval(Value::from(source)), NodeResult::Boolean(true));
+    }
+}
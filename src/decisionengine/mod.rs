@@ -4,75 +4,256 @@ extern crate serde_json;
 
 use serde_json::Value;
 
+pub mod cache;
 pub mod datasource;
+pub mod decisions;
 pub mod deserializers;
+pub mod errors;
+pub mod expressions;
 pub mod modules;
 pub mod nodes;
 pub mod operations;
 pub mod results;
 pub mod rules;
 pub mod schema;
-pub mod visitor;
+pub mod scorecard;
+#[cfg(test)]
+pub mod testing;
+pub mod types;
+pub mod validation;
+pub mod variables;
 
+use chrono::{DateTime, Utc};
 use decisionengine::datasource::DecisionDataset;
-use decisionengine::modules::PassAllModule;
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::modules::ModuleChildren;
+use decisionengine::results::SubmoduleResult;
 use decisionengine::schema::decision_strategy;
-use decisionengine::visitor::DecisionTreeVisitor;
+use decisionengine::validation::validate_strategy;
+use decisionengine::variables::{deserialize_variables, Variables};
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
+use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::prelude::*;
+use std::sync::Arc;
 
-#[derive(PartialEq, Clone, Deserialize, Serialize)]
+#[derive(PartialEq, Clone, Debug, Deserialize, Serialize)]
 pub enum EvalResult {
     Accept,
+    Refer,
     Reject,
 }
 
+/// Whether detailed evaluation stops evaluating the children of a module once
+/// the module's result is decided.
+#[derive(PartialEq, Clone, Copy)]
+pub enum EvalMode {
+    /// Evaluates only the children that `eval` would.
+    ShortCircuit,
+    /// Also evaluates the children after a module's result is decided, so
+    /// that every node has a result. These children do not change the
+    /// module's result or add reasons.
+    Exhaustive,
+}
+
+/// Evaluation only reads the strategy. Everything that changes during a
+/// decision, such as the fetched data, reasons and variable values, lives in
+/// the `DecisionDataset`, so one loaded strategy can evaluate decisions on
+/// many threads at once.
 pub trait Evaluatable {
-    fn eval(&mut self, input: &mut DecisionDataset) -> EvalResult;
-    fn accept<V: DecisionTreeVisitor>(&mut self, visitor: &mut V);
+    fn eval(&self, input: &mut DecisionDataset) -> Result<EvalResult, DecisionEngineError>;
+    /// Evaluates in a single pass, returning the result of every module, rule
+    /// and condition evaluated along with the overall result.
+    fn eval_detailed(
+        &self,
+        input: &mut DecisionDataset,
+        mode: EvalMode,
+    ) -> Result<SubmoduleResult, DecisionEngineError>;
+}
+
+/// A deserialized decision strategy: its top-level module, and the variables
+/// that the conditions within it can reference.
+pub struct Strategy {
+    variables: Arc<Variables>,
+    module: ModuleChildren,
+}
+
+impl Evaluatable for Strategy {
+    fn eval(&self, input: &mut DecisionDataset) -> Result<EvalResult, DecisionEngineError> {
+        input.bind_variables(self.variables.clone());
+        self.module.eval(input)
+    }
+
+    fn eval_detailed(
+        &self,
+        input: &mut DecisionDataset,
+        mode: EvalMode,
+    ) -> Result<SubmoduleResult, DecisionEngineError> {
+        input.bind_variables(self.variables.clone());
+        let mut result = self.module.eval_detailed(input, mode)?;
+        if let SubmoduleResult::ModuleResult(ref mut res) = result {
+            res.variables = input
+                .variable_values()
+                .iter()
+                .map(|(name, value)| (name.clone(), value.to_json()))
+                .collect();
+        }
+        Ok(result)
+    }
+}
+
+impl Strategy {
+    /// Canonical JSON form of the strategy, which deserializes to an
+    /// equivalent strategy.
+    pub fn serialize(&self) -> Value {
+        let mut value = self.module.serialize();
+        if !self.variables.is_empty() {
+            if let Value::Object(ref mut strategy) = value {
+                strategy.insert(String::from("variables"), self.variables.serialize());
+            }
+        }
+        value
+    }
+}
+
+/// Fails to compile if a loaded strategy can no longer be shared between
+/// threads, or a dataset moved between them.
+#[allow(dead_code)]
+fn assert_thread_safe() {
+    fn shared<T: Send + Sync>() {}
+    fn sent<T: Send>() {}
+    shared::<Strategy>();
+    sent::<DecisionDataset>();
+}
+
+pub fn deserialize_strategy(value: &Value) -> Result<Strategy, DecisionEngineError> {
+    Ok(Strategy {
+        variables: Arc::new(deserialize_variables(&value["variables"])?),
+        module: self::modules::deserialize_module(value)?,
+    })
+}
+
+/// Validates and type checks a strategy before deserializing it, so that a
+/// strategy is never evaluated unchecked wherever it was loaded from.
+pub fn load_strategy(value: &Value) -> Result<Strategy, DecisionEngineError> {
+    let errors = validate_strategy(value);
+    if !errors.is_empty() {
+        return Err(DecisionEngineError::InvalidStrategy(errors));
+    }
+    deserialize_strategy(value)
 }
 
 #[derive(Queryable)]
 pub struct DecisionStrategy {
     decision_strategy_id: i32,
     decision_strategy_json: serde_json::Value,
+    created_at: DateTime<Utc>,
+    retired_at: Option<DateTime<Utc>>,
+}
+
+/// Summary of a stored strategy, without its JSON.
+#[derive(Serialize)]
+pub struct DecisionStrategyMetadata {
+    pub decision_strategy_id: i32,
+    pub module_name: Option<String>,
+    pub content_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub retired_at: Option<DateTime<Utc>>,
 }
 
 impl DecisionStrategy {
-    pub fn from_id(id: i32, connection: &PgConnection) -> Self {
+    pub fn from_id(id: i32, connection: &PgConnection) -> Result<Self, DecisionEngineError> {
         use decisionengine::schema::decision_strategy::dsl::*;
 
-        let ds: DecisionStrategy = decision_strategy
+        decision_strategy
             .find(id)
             .first::<DecisionStrategy>(connection)
-            .expect("Error loading decision strategy");
+            .optional()?
+            .ok_or(DecisionEngineError::StrategyNotFound(id))
+    }
 
-        ds
+    /// Every stored strategy, including retired ones, in the order they
+    /// were created.
+    pub fn list(connection: &PgConnection) -> Result<Vec<Self>, DecisionEngineError> {
+        use decisionengine::schema::decision_strategy::dsl::*;
+
+        Ok(decision_strategy
+            .order(decision_strategy_id)
+            .load::<DecisionStrategy>(connection)?)
+    }
+
+    /// Marks the strategy as retired so that no further decisions are made
+    /// with it. Retiring an already retired strategy keeps its original
+    /// retirement time.
+    pub fn retire(id: i32, connection: &PgConnection) -> Result<Self, DecisionEngineError> {
+        use decisionengine::schema::decision_strategy::dsl::*;
+
+        let retired = diesel::update(decision_strategy.find(id).filter(retired_at.is_null()))
+            .set(retired_at.eq(Some(Utc::now())))
+            .get_result::<DecisionStrategy>(connection)
+            .optional()?;
+        match retired {
+            Some(retired) => Ok(retired),
+            None => DecisionStrategy::from_id(id, connection),
+        }
     }
 
     pub fn decision_strategy_id(&self) -> i32 {
         self.decision_strategy_id
     }
 
-    pub fn get_module(&self) -> Box<PassAllModule> {
-        Box::from(self::modules::deserialize_module(
-            &self.decision_strategy_json,
-        ))
+    pub fn decision_strategy_json(&self) -> &Value {
+        &self.decision_strategy_json
+    }
+
+    pub fn is_retired(&self) -> bool {
+        self.retired_at.is_some()
+    }
+
+    pub fn metadata(&self) -> DecisionStrategyMetadata {
+        DecisionStrategyMetadata {
+            decision_strategy_id: self.decision_strategy_id,
+            module_name: self.decision_strategy_json["module_name"]
+                .as_str()
+                .map(String::from),
+            content_hash: self.content_hash(),
+            created_at: self.created_at,
+            retired_at: self.retired_at,
+        }
+    }
+
+    /// Loads the stored strategy, which is checked again in case it was
+    /// stored before the current checks existed.
+    pub fn get_strategy(&self) -> Result<Strategy, DecisionEngineError> {
+        load_strategy(&self.decision_strategy_json)
     }
 
-    pub fn create(json: Value, connection: &PgConnection) -> DecisionStrategy {
+    /// Hex SHA-256 of the strategy JSON, which identifies the exact strategy
+    /// a decision was made with.
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::default();
+        hasher.input(self.decision_strategy_json.to_string().as_bytes());
+        hasher
+            .result()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn create(
+        json: Value,
+        connection: &PgConnection,
+    ) -> Result<DecisionStrategy, DecisionEngineError> {
         use decisionengine::schema::decision_strategy;
 
         let new_decision_strategy = NewDecisionStrategy {
             decision_strategy_json: json,
         };
 
-        diesel::insert_into(decision_strategy::table)
+        Ok(diesel::insert_into(decision_strategy::table)
             .values(&new_decision_strategy)
-            .get_result(connection)
-            .expect("Error saving new post")
+            .get_result(connection)?)
     }
 }
 
@@ -85,19 +266,282 @@ struct NewDecisionStrategy {
 pub struct DecisionEngine {}
 
 impl DecisionEngine {
-    pub fn from_file(file: &mut File) -> Box<PassAllModule> {
+    pub fn from_file(file: &mut File) -> Result<Strategy, DecisionEngineError> {
         let mut serialized_decision_strategy = String::new();
-        file.read_to_string(&mut serialized_decision_strategy)
-            .expect("Something went wrong while reading the decision_strategy file");
+        file.read_to_string(&mut serialized_decision_strategy)?;
 
         let decision_module_json: Value = match serde_json::from_str(&serialized_decision_strategy)
         {
             Ok(json) => json,
-            Err(error) => panic!(format!("Malformed JSON: {}", error)),
+            Err(error) => {
+                return Err(DecisionEngineError::MalformedStrategy(format!(
+                    "Malformed JSON: {}",
+                    error
+                )))
+            }
         };
 
-        Box::from(self::modules::deserialize_module(&decision_module_json))
+        load_strategy(&decision_module_json)
     }
 }
 
 pub fn create() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decisionengine::testing::{dataset, detailed, rule, rule_strategy, stored};
+
+    const PRECISE: &str = "0.1234567890123456789012345";
+
+    fn precise() -> Value {
+        serde_json::from_str(PRECISE).unwrap()
+    }
+
+    fn round_trip(original: &Value) -> Value {
+        let serialized = deserialize_strategy(original).unwrap().serialize();
+        let reserialized = deserialize_strategy(&serialized).unwrap().serialize();
+        assert_eq!(serialized, reserialized);
+        serialized
+    }
+
+    fn from_json(strategy: &Value, name: &str) -> Result<Strategy, DecisionEngineError> {
+        let path = std::env::temp_dir().join(format!("decisionengine-{}.json", name));
+        std::fs::write(&path, strategy.to_string()).unwrap();
+        let result = DecisionEngine::from_file(&mut File::open(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn from_file_rejects_invalid_strategies() {
+        let strategy = rule_strategy(Value::from("application_data_v1.shoe_size > 1"));
+        match from_json(&strategy, "invalid") {
+            Err(DecisionEngineError::InvalidStrategy(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].pointer, "/children/0/conditions/0/condition");
+            }
+            _ => panic!("expected the strategy to be invalid"),
+        }
+        assert!(from_json(&rule_strategy(Value::from("true")), "valid").is_ok());
+    }
+
+    #[test]
+    fn one_strategy_evaluates_on_many_threads() {
+        let mut strategy = rule_strategy(Value::from("adult"));
+        strategy["variables"] = json!({"adult": "application_data_v1.age >= 18"});
+        let strategy = Arc::new(deserialize_strategy(&strategy).unwrap());
+
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let strategy = strategy.clone();
+                std::thread::spawn(move || {
+                    let age = 14 + thread;
+                    for _ in 0..100 {
+                        let mut input = dataset(json!({ "age": age }));
+                        let details = strategy
+                            .eval_detailed(&mut input, EvalMode::Exhaustive)
+                            .unwrap();
+                        let details = serde_json::to_value(&details).unwrap();
+                        assert_eq!(details["variables"]["adult"], age >= 18);
+                        assert_eq!(
+                            strategy.eval(&mut dataset(json!({ "age": age }))).unwrap(),
+                            if age >= 18 {
+                                EvalResult::Accept
+                            } else {
+                                EvalResult::Reject
+                            }
+                        );
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn content_hash_identifies_the_strategy_json() {
+        let hash = stored(rule_strategy(Value::from("true"))).content_hash();
+        assert_eq!(hash.len(), 64);
+        assert!(hash.chars().all(|c| c.is_digit(16) && !c.is_uppercase()));
+        assert_eq!(
+            hash,
+            stored(rule_strategy(Value::from("true"))).content_hash()
+        );
+        assert!(hash != stored(rule_strategy(Value::from("false"))).content_hash());
+    }
+
+    #[test]
+    fn metadata_summarises_the_stored_strategy() {
+        let mut strategy = stored(rule_strategy(Value::from("true")));
+        let metadata = strategy.metadata();
+        assert_eq!(metadata.decision_strategy_id, 1);
+        assert_eq!(metadata.module_name, Some(String::from("Test")));
+        assert_eq!(metadata.content_hash, strategy.content_hash());
+        assert_eq!(metadata.retired_at, None);
+        assert!(!strategy.is_retired());
+
+        strategy.retired_at = Some(Utc::now());
+        assert!(strategy.is_retired());
+        assert_eq!(strategy.metadata().retired_at, strategy.retired_at);
+    }
+
+    #[test]
+    fn bureau_data_holds_only_the_sources_read() {
+        let strategy = deserialize_strategy(&rule_strategy(Value::from("true"))).unwrap();
+        let mut input = dataset(json!({}));
+        strategy.eval(&mut input).unwrap();
+        assert_eq!(input.bureau_data(), json!({}));
+
+        let strategy = deserialize_strategy(&rule_strategy(Value::from(
+            "any(experian_v1_1.searches, true)",
+        )))
+        .unwrap();
+        let mut input = dataset(json!({}));
+        strategy.eval(&mut input).unwrap();
+        let bureau_data = input.bureau_data();
+        assert_eq!(bureau_data.as_object().unwrap().len(), 1);
+        assert_eq!(
+            bureau_data["experian_v1_1"]["searches"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn examples_round_trip() {
+        let examples = [
+            include_str!("../../examples/1/ruleset.json"),
+            include_str!("../../examples/2/ruleset.json"),
+            include_str!("../../examples/3/ruleset.json"),
+            include_str!("../../examples/4/ruleset.json"),
+        ];
+        for example in examples.iter() {
+            round_trip(&serde_json::from_str(example).unwrap());
+        }
+    }
+
+    #[test]
+    fn every_kind_of_node_round_trips() {
+        let mut goto = rule(
+            2,
+            Value::from("substring(application_data_v1.first_name, 0, 2) == \"To\""),
+        );
+        goto["conditions"][0]["true"] = json!({"type": "goto", "value": "2"});
+        goto["conditions"].as_array_mut().unwrap().push(json!({
+            "type": "condition",
+            "condition_id": "2",
+            "condition": "application_data_v1.income > 1000",
+            "true": {"type": "return", "value": "ACCEPT"},
+            "false": {"type": "return", "value": "REFER", "message": "Low income"},
+            "on_missing": {"type": "return", "value": "REFER"},
+        }));
+        let original = json!({
+            "type": "module",
+            "module_type": "all",
+            "module_name": "Everything",
+            "precedence": ["REJECT", "REFER", "ACCEPT"],
+            "variables": {"adult": "application_data_v1.age >= 18"},
+            "children": [
+                {
+                    "type": "module",
+                    "module_type": "any",
+                    "module_name": "Any",
+                    "children": [
+                        rule(1, Value::from("adult && days_between(date(\"2018-01-01\"), date(now())) > 0")),
+                        goto,
+                    ],
+                },
+                {
+                    "type": "module",
+                    "module_type": "threshold",
+                    "module_name": "Threshold",
+                    "threshold": 1,
+                    "children": [rule(3, Value::from(
+                        "!any(experian_v1_1.accounts, element.status == \"default\" && element.balance > 1000)"
+                    ))],
+                },
+                {
+                    "type": "module",
+                    "module_type": "first-match",
+                    "module_name": "First match",
+                    "default": "REFER",
+                    "children": [rule(4, Value::from("-sum(map(experian_v1_1.accounts, element.balance)) < 0"))],
+                },
+                {
+                    "type": "module",
+                    "module_type": "scorecard",
+                    "module_name": "Score",
+                    "characteristics": [{
+                        "characteristic_name": "Age",
+                        "value": {"type": "input", "value": "application_data_v1.age"},
+                        "bins": [{"max": 25, "points": 5}, {"min": 25, "points": 20}],
+                    }],
+                    "cutoffs": [{"min_score": 10, "result": "ACCEPT", "reason_code": "SCORED"}],
+                    "default": "REJECT",
+                },
+            ],
+        });
+        let serialized = round_trip(&original);
+
+        let data = json!({"income": 2000});
+        assert_eq!(
+            detailed(&serialized, data.clone(), EvalMode::Exhaustive).unwrap(),
+            detailed(&original, data, EvalMode::Exhaustive).unwrap()
+        );
+    }
+
+    #[test]
+    fn serialize_keeps_constant_precision() {
+        let original = rule_strategy(json!({
+            "type": "op",
+            "op": "==",
+            "lvalue": {"type": "input", "value": "application_data_v1.income"},
+            "rvalue": {"type": "constant", "value": precise()},
+        }));
+        let serialized = round_trip(&original);
+        assert_eq!(
+            serialized["children"][0]["conditions"][0]["condition"]["rvalue"]["value"].to_string(),
+            PRECISE
+        );
+
+        let strategy = deserialize_strategy(&serialized).unwrap();
+        let mut input = dataset(json!({ "income": precise() }));
+        assert_eq!(strategy.eval(&mut input).unwrap(), EvalResult::Accept);
+        let rounded: Value = serde_json::from_str("0.12345678901234568").unwrap();
+        let mut input = dataset(json!({ "income": rounded }));
+        assert_eq!(strategy.eval(&mut input).unwrap(), EvalResult::Reject);
+    }
+
+    #[test]
+    fn serialize_keeps_scorecard_bound_precision() {
+        let original = json!({
+            "type": "module",
+            "module_type": "scorecard",
+            "module_name": "Income",
+            "characteristics": [{
+                "characteristic_name": "Income",
+                "value": {"type": "input", "value": "application_data_v1.income"},
+                "bins": [{"min": precise(), "points": 10}],
+            }],
+            "cutoffs": [{"min_score": 10, "result": "ACCEPT"}],
+            "default": "REJECT",
+        });
+        let serialized = round_trip(&original);
+        assert_eq!(
+            serialized["characteristics"][0]["bins"][0]["min"].to_string(),
+            PRECISE
+        );
+
+        let strategy = deserialize_strategy(&serialized).unwrap();
+        let mut input = dataset(json!({ "income": precise() }));
+        assert_eq!(strategy.eval(&mut input).unwrap(), EvalResult::Accept);
+        let below: Value = serde_json::from_str("0.1234567890123456789012344").unwrap();
+        let mut input = dataset(json!({ "income": below }));
+        assert_eq!(strategy.eval(&mut input).unwrap(), EvalResult::Reject);
+    }
+}
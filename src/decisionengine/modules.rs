@@ -1,90 +1,1087 @@
 extern crate serde_json;
 
 use decisionengine::datasource::DecisionDataset;
+use decisionengine::deserializers::{get_array, get_str, get_u64};
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::results::{FailedResult, ModuleResult, SubmoduleResult};
 use decisionengine::rules::Rule;
-use decisionengine::visitor::DecisionTreeVisitor;
-use serde_json::Value;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 
-use decisionengine::rules::deserialize_rule;
-use decisionengine::{EvalResult, Evaluatable};
+use decisionengine::rules::{deserialize_eval_result, deserialize_rule, serialize_eval_result};
+use decisionengine::scorecard::{deserialize_scorecard_module, ScorecardModule};
+use decisionengine::{EvalMode, EvalResult, Evaluatable};
 
 pub enum ModuleChildren {
     PassAllModule(PassAllModule),
+    PassAnyModule(PassAnyModule),
+    ThresholdModule(ThresholdModule),
+    FirstMatchModule(FirstMatchModule),
+    ScorecardModule(ScorecardModule),
     Rule(Rule),
 }
 
-trait Module {}
+impl Evaluatable for ModuleChildren {
+    fn eval(&self, input: &mut DecisionDataset) -> Result<EvalResult, DecisionEngineError> {
+        // Only the reasons that support a module's outcome are kept, so
+        // e.g. a rejecting rule inside an accepting "any" module is dropped.
+        let first_reason = input.reasons().len();
+        let result = match self {
+            ModuleChildren::PassAllModule(module) => module.eval(input),
+            ModuleChildren::PassAnyModule(module) => module.eval(input),
+            ModuleChildren::ThresholdModule(module) => module.eval(input),
+            ModuleChildren::FirstMatchModule(module) => module.eval(input),
+            ModuleChildren::ScorecardModule(module) => module.eval(input),
+            ModuleChildren::Rule(rule) => return rule.eval(input),
+        }?;
+        input.retain_reasons(first_reason, &result);
+        Ok(result)
+    }
+
+    fn eval_detailed(
+        &self,
+        input: &mut DecisionDataset,
+        mode: EvalMode,
+    ) -> Result<SubmoduleResult, DecisionEngineError> {
+        let first_reason = input.reasons().len();
+        let result = match self {
+            ModuleChildren::PassAllModule(module) => module.eval_detailed(input, mode),
+            ModuleChildren::PassAnyModule(module) => module.eval_detailed(input, mode),
+            ModuleChildren::ThresholdModule(module) => module.eval_detailed(input, mode),
+            ModuleChildren::FirstMatchModule(module) => module.eval_detailed(input, mode),
+            ModuleChildren::ScorecardModule(module) => module.eval_detailed(input, mode),
+            ModuleChildren::Rule(rule) => return rule.eval_detailed(input, mode),
+        }?;
+        if let Some(module_result) = result.result() {
+            input.retain_reasons(first_reason, module_result);
+        }
+        Ok(result)
+    }
+}
+
+/// Where a module evaluated in detail collects the results of its children.
+type DetailedResults<'a> = Option<(EvalMode, &'a mut Vec<SubmoduleResult>)>;
+
+/// Evaluates children in order, passing each result to `decide` until it
+/// returns true or, if `decided` is already true, evaluating none.
+///
+/// In detailed evaluation the result of each child is also collected, and in
+/// exhaustive mode the children after the decisive one are evaluated too.
+/// These children must not change the outcome, so their results are not
+/// decided on, their reasons are dropped, the steps they take are not
+/// counted against the budget and their failures are recorded in their
+/// results rather than failing the decision.
+fn eval_children<F>(
+    children: &[ModuleChildren],
+    input: &mut DecisionDataset,
+    detailed: DetailedResults,
+    mut decided: bool,
+    mut decide: F,
+) -> Result<(), DecisionEngineError>
+where
+    F: FnMut(EvalResult) -> bool,
+{
+    match detailed {
+        None => {
+            for child in children {
+                if decided {
+                    break;
+                }
+                decided = decide(child.eval(input)?);
+            }
+        }
+        Some((mode, results)) => {
+            for child in children {
+                if !decided {
+                    let result = child.eval_detailed(input, mode)?;
+                    if let Some(child_result) = result.result() {
+                        decided = decide(child_result.clone());
+                    }
+                    results.push(result);
+                } else if mode == EvalMode::Exhaustive {
+                    let first_reason = input.reasons().len();
+                    let steps = input.steps();
+                    let result = child
+                        .eval_detailed(input, mode)
+                        .unwrap_or_else(|error| child.failed_result(&error));
+                    input.truncate_reasons(first_reason);
+                    input.reset_steps(steps);
+                    results.push(result);
+                } else {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn module_identity<M: Module>(module: &M) -> (Option<String>, Option<String>, Option<i32>) {
+    (
+        Some(module.module_name().to_string()),
+        Some(module.module_type().to_string()),
+        None,
+    )
+}
+
+/// The detailed result of a module, to which modules such as scorecards add
+/// their own details.
+pub fn module_result<M: Module>(
+    module: &M,
+    result: EvalResult,
+    submodule_results: Vec<SubmoduleResult>,
+) -> ModuleResult {
+    ModuleResult {
+        module_id: module.module_name().to_string(),
+        module_type: module.module_type().to_string(),
+        result: result,
+        submodule_results: submodule_results,
+        score: None,
+        characteristic_results: Vec::new(),
+        variables: BTreeMap::new(),
+    }
+}
+
+impl ModuleChildren {
+    /// Detailed result of the module or rule when it failed with `error`.
+    fn failed_result(&self, error: &DecisionEngineError) -> SubmoduleResult {
+        let (module_id, module_type, rule_id) = match self {
+            ModuleChildren::PassAllModule(module) => module_identity(module),
+            ModuleChildren::PassAnyModule(module) => module_identity(module),
+            ModuleChildren::ThresholdModule(module) => module_identity(module),
+            ModuleChildren::FirstMatchModule(module) => module_identity(module),
+            ModuleChildren::ScorecardModule(module) => module_identity(module),
+            ModuleChildren::Rule(rule) => (None, None, Some(rule.rule_id)),
+        };
+        SubmoduleResult::FailedResult(FailedResult {
+            module_id: module_id,
+            module_type: module_type,
+            rule_id: rule_id,
+            error: error.to_string(),
+        })
+    }
+
+    /// Canonical JSON form of the module or rule, which deserializes to an
+    /// equivalent tree.
+    pub fn serialize(&self) -> Value {
+        match self {
+            ModuleChildren::PassAllModule(module) => module.serialize(),
+            ModuleChildren::PassAnyModule(module) => module.serialize(),
+            ModuleChildren::ThresholdModule(module) => module.serialize(),
+            ModuleChildren::FirstMatchModule(module) => module.serialize(),
+            ModuleChildren::ScorecardModule(module) => module.serialize(),
+            ModuleChildren::Rule(rule) => rule.serialize(),
+        }
+    }
+}
+
+/// The fields shared by every serialized module, to which each module adds
+/// its own.
+pub fn module_json<M: Module>(module: &M) -> Map<String, Value> {
+    let mut json = Map::new();
+    json.insert(String::from("type"), Value::from("module"));
+    json.insert(
+        String::from("module_type"),
+        Value::from(module.module_type()),
+    );
+    json.insert(
+        String::from("module_name"),
+        Value::from(module.module_name()),
+    );
+    json
+}
+
+/// Serializes a module with children, writing its precedence only if it is
+/// not the default.
+fn serialize_parent<M: Module>(
+    module: &M,
+    precedence: Option<&Precedence>,
+    children: &[ModuleChildren],
+) -> Map<String, Value> {
+    let mut json = module_json(module);
+    if let Some(precedence) = precedence.filter(|p| **p != Precedence::default()) {
+        json.insert(String::from("precedence"), precedence.serialize());
+    }
+    json.insert(
+        String::from("children"),
+        Value::Array(children.iter().map(ModuleChildren::serialize).collect()),
+    );
+    json
+}
+
+pub trait Module: Evaluatable {
+    fn module_name(&self) -> &str;
+    fn module_type(&self) -> &'static str;
+}
+
+impl Module for PassAllModule {
+    fn module_name(&self) -> &str {
+        &self.module_name
+    }
+
+    fn module_type(&self) -> &'static str {
+        "all"
+    }
+}
+
+impl Module for PassAnyModule {
+    fn module_name(&self) -> &str {
+        &self.module_name
+    }
+
+    fn module_type(&self) -> &'static str {
+        "any"
+    }
+}
+
+impl Module for ThresholdModule {
+    fn module_name(&self) -> &str {
+        &self.module_name
+    }
+
+    fn module_type(&self) -> &'static str {
+        "threshold"
+    }
+}
+
+impl Module for FirstMatchModule {
+    fn module_name(&self) -> &str {
+        &self.module_name
+    }
+
+    fn module_type(&self) -> &'static str {
+        "first-match"
+    }
+}
+
+/// Order in which a module combines the outcomes of its children, highest
+/// precedence first. Defaults to reject > refer > accept.
+#[derive(Clone, PartialEq)]
+pub struct Precedence {
+    order: Vec<EvalResult>,
+}
+
+impl Precedence {
+    pub fn new(order: Vec<EvalResult>) -> Result<Self, DecisionEngineError> {
+        for result in &[EvalResult::Accept, EvalResult::Refer, EvalResult::Reject] {
+            if order.iter().filter(|r| *r == result).count() != 1 {
+                return Err(DecisionEngineError::MalformedStrategy(String::from(
+                    "Precedence must list every outcome exactly once.",
+                )));
+            }
+        }
+        Ok(Self { order: order })
+    }
+
+    fn rank(&self, result: &EvalResult) -> usize {
+        self.order.iter().position(|r| r == result).unwrap()
+    }
+
+    pub fn highest(&self) -> EvalResult {
+        self.order[0].clone()
+    }
+
+    pub fn lowest(&self) -> EvalResult {
+        self.order[self.order.len() - 1].clone()
+    }
 
-impl Module for PassAllModule {}
+    pub fn max(&self, a: EvalResult, b: EvalResult) -> EvalResult {
+        if self.rank(&b) < self.rank(&a) {
+            b
+        } else {
+            a
+        }
+    }
+
+    pub fn min(&self, a: EvalResult, b: EvalResult) -> EvalResult {
+        if self.rank(&b) > self.rank(&a) {
+            b
+        } else {
+            a
+        }
+    }
+
+    pub fn serialize(&self) -> Value {
+        Value::Array(self.order.iter().map(serialize_eval_result).collect())
+    }
+}
 
+impl Default for Precedence {
+    fn default() -> Self {
+        Self {
+            order: vec![EvalResult::Reject, EvalResult::Refer, EvalResult::Accept],
+        }
+    }
+}
+
+/// Returns the highest precedence outcome among its children, so with the
+/// default precedence it accepts only if every child accepts.
 pub struct PassAllModule {
     pub module_name: String,
+    pub precedence: Precedence,
     pub children: Vec<ModuleChildren>,
 }
 
 impl PassAllModule {
-    pub fn new(module_name: String, children: Vec<ModuleChildren>) -> Self {
+    pub fn new(module_name: String, precedence: Precedence, children: Vec<ModuleChildren>) -> Self {
         Self {
             module_name: module_name,
+            precedence: precedence,
             children: children,
         }
     }
 }
 
+impl PassAllModule {
+    fn combine(
+        &self,
+        input: &mut DecisionDataset,
+        detailed: DetailedResults,
+    ) -> Result<EvalResult, DecisionEngineError> {
+        let precedence = &self.precedence;
+        let highest = precedence.highest();
+        let mut result = precedence.lowest();
+        eval_children(&self.children, input, detailed, false, |child| {
+            result = precedence.max(result.clone(), child);
+            result == highest
+        })?;
+        Ok(result)
+    }
+
+    pub fn serialize(&self) -> Value {
+        Value::Object(serialize_parent(
+            self,
+            Some(&self.precedence),
+            &self.children,
+        ))
+    }
+}
+
 impl Evaluatable for PassAllModule {
-    fn eval(&mut self, input: &mut DecisionDataset) -> EvalResult {
-        for child in &mut self.children {
-            let result = match child {
-                ModuleChildren::Rule(rule) => rule.eval(input),
-                ModuleChildren::PassAllModule(module) => module.eval(input),
-            };
-            if result == EvalResult::Reject {
-                return EvalResult::Reject;
-            }
+    fn eval(&self, input: &mut DecisionDataset) -> Result<EvalResult, DecisionEngineError> {
+        self.combine(input, None)
+    }
+
+    fn eval_detailed(
+        &self,
+        input: &mut DecisionDataset,
+        mode: EvalMode,
+    ) -> Result<SubmoduleResult, DecisionEngineError> {
+        let mut submodule_results = Vec::new();
+        let result = self.combine(input, Some((mode, &mut submodule_results)))?;
+        Ok(SubmoduleResult::ModuleResult(module_result(
+            self,
+            result,
+            submodule_results,
+        )))
+    }
+}
+
+/// Returns the lowest precedence outcome among its children, so with the
+/// default precedence it accepts if any child accepts.
+pub struct PassAnyModule {
+    pub module_name: String,
+    pub precedence: Precedence,
+    pub children: Vec<ModuleChildren>,
+}
+
+impl PassAnyModule {
+    pub fn new(module_name: String, precedence: Precedence, children: Vec<ModuleChildren>) -> Self {
+        Self {
+            module_name: module_name,
+            precedence: precedence,
+            children: children,
+        }
+    }
+}
+
+impl PassAnyModule {
+    fn combine(
+        &self,
+        input: &mut DecisionDataset,
+        detailed: DetailedResults,
+    ) -> Result<EvalResult, DecisionEngineError> {
+        let precedence = &self.precedence;
+        let lowest = precedence.lowest();
+        let mut result = precedence.highest();
+        eval_children(&self.children, input, detailed, false, |child| {
+            result = precedence.min(result.clone(), child);
+            result == lowest
+        })?;
+        Ok(result)
+    }
+
+    pub fn serialize(&self) -> Value {
+        Value::Object(serialize_parent(
+            self,
+            Some(&self.precedence),
+            &self.children,
+        ))
+    }
+}
+
+impl Evaluatable for PassAnyModule {
+    fn eval(&self, input: &mut DecisionDataset) -> Result<EvalResult, DecisionEngineError> {
+        self.combine(input, None)
+    }
+
+    fn eval_detailed(
+        &self,
+        input: &mut DecisionDataset,
+        mode: EvalMode,
+    ) -> Result<SubmoduleResult, DecisionEngineError> {
+        let mut submodule_results = Vec::new();
+        let result = self.combine(input, Some((mode, &mut submodule_results)))?;
+        Ok(SubmoduleResult::ModuleResult(module_result(
+            self,
+            result,
+            submodule_results,
+        )))
+    }
+}
+
+/// Accepts if at least `threshold` of its children accept. Otherwise returns
+/// the highest precedence outcome among the children that did not accept.
+pub struct ThresholdModule {
+    pub module_name: String,
+    pub threshold: usize,
+    pub precedence: Precedence,
+    pub children: Vec<ModuleChildren>,
+}
+
+impl ThresholdModule {
+    pub fn new(
+        module_name: String,
+        threshold: usize,
+        precedence: Precedence,
+        children: Vec<ModuleChildren>,
+    ) -> Self {
+        Self {
+            module_name: module_name,
+            threshold: threshold,
+            precedence: precedence,
+            children: children,
         }
-        EvalResult::Accept
     }
+}
 
-    fn accept<V: DecisionTreeVisitor>(&mut self, visitor: &mut V) {
-        visitor.visit_pass_all_module(self);
-        for child in &mut self.children {
+impl ThresholdModule {
+    fn combine(
+        &self,
+        input: &mut DecisionDataset,
+        detailed: DetailedResults,
+    ) -> Result<EvalResult, DecisionEngineError> {
+        let threshold = self.threshold;
+        let precedence = &self.precedence;
+        let mut accepted = 0;
+        let mut remaining = self.children.len();
+        let mut failed: Option<EvalResult> = None;
+        let decided = threshold == 0 || remaining < threshold;
+        eval_children(&self.children, input, detailed, decided, |child| {
             match child {
-                ModuleChildren::PassAllModule(m) => {
-                    m.accept(visitor);
-                }
-                ModuleChildren::Rule(r) => {
-                    r.accept(visitor);
+                EvalResult::Accept => accepted += 1,
+                result => {
+                    failed = Some(match failed.take() {
+                        Some(f) => precedence.max(f, result),
+                        None => result,
+                    })
                 }
             }
+            remaining -= 1;
+            accepted >= threshold || accepted + remaining < threshold
+        })?;
+        if accepted >= threshold {
+            Ok(EvalResult::Accept)
+        } else {
+            Ok(failed.unwrap_or(EvalResult::Reject))
         }
-        visitor.leave_pass_all_module(self);
+    }
+
+    pub fn serialize(&self) -> Value {
+        let mut json = serialize_parent(self, Some(&self.precedence), &self.children);
+        json.insert(
+            String::from("threshold"),
+            Value::from(self.threshold as u64),
+        );
+        Value::Object(json)
+    }
+}
+
+impl Evaluatable for ThresholdModule {
+    fn eval(&self, input: &mut DecisionDataset) -> Result<EvalResult, DecisionEngineError> {
+        self.combine(input, None)
+    }
+
+    fn eval_detailed(
+        &self,
+        input: &mut DecisionDataset,
+        mode: EvalMode,
+    ) -> Result<SubmoduleResult, DecisionEngineError> {
+        let mut submodule_results = Vec::new();
+        let result = self.combine(input, Some((mode, &mut submodule_results)))?;
+        Ok(SubmoduleResult::ModuleResult(module_result(
+            self,
+            result,
+            submodule_results,
+        )))
+    }
+}
+
+/// Waterfall module: returns the result of the first child whose result
+/// differs from `default`, or `default` if no child is decisive.
+pub struct FirstMatchModule {
+    pub module_name: String,
+    pub default: EvalResult,
+    pub children: Vec<ModuleChildren>,
+}
+
+impl FirstMatchModule {
+    pub fn new(module_name: String, default: EvalResult, children: Vec<ModuleChildren>) -> Self {
+        Self {
+            module_name: module_name,
+            default: default,
+            children: children,
+        }
+    }
+}
+
+impl FirstMatchModule {
+    fn combine(
+        &self,
+        input: &mut DecisionDataset,
+        detailed: DetailedResults,
+    ) -> Result<EvalResult, DecisionEngineError> {
+        let default = &self.default;
+        let mut matched = None;
+        eval_children(&self.children, input, detailed, false, |child| {
+            if child != *default {
+                matched = Some(child);
+            }
+            matched.is_some()
+        })?;
+        Ok(matched.unwrap_or_else(|| self.default.clone()))
+    }
+
+    pub fn serialize(&self) -> Value {
+        let mut json = serialize_parent(self, None, &self.children);
+        json.insert(
+            String::from("default"),
+            serialize_eval_result(&self.default),
+        );
+        Value::Object(json)
+    }
+}
+
+impl Evaluatable for FirstMatchModule {
+    fn eval(&self, input: &mut DecisionDataset) -> Result<EvalResult, DecisionEngineError> {
+        self.combine(input, None)
+    }
+
+    fn eval_detailed(
+        &self,
+        input: &mut DecisionDataset,
+        mode: EvalMode,
+    ) -> Result<SubmoduleResult, DecisionEngineError> {
+        let mut submodule_results = Vec::new();
+        let result = self.combine(input, Some((mode, &mut submodule_results)))?;
+        Ok(SubmoduleResult::ModuleResult(module_result(
+            self,
+            result,
+            submodule_results,
+        )))
     }
 }
 
-pub fn deserialize_module_children(value: &Value) -> ModuleChildren {
-    let child_type = value["type"].as_str().unwrap();
-    if child_type == "rule" {
-        return ModuleChildren::Rule(deserialize_rule(value));
+pub fn deserialize_module_children(value: &Value) -> Result<ModuleChildren, DecisionEngineError> {
+    if get_str(value, "type")? == "rule" {
+        Ok(ModuleChildren::Rule(deserialize_rule(value)?))
     } else {
-        return ModuleChildren::PassAllModule(deserialize_module(value));
+        deserialize_module(value)
     }
 }
 
-pub fn deserialize_module(value: &Value) -> PassAllModule {
-    let children: Vec<ModuleChildren> = value["children"]
-        .as_array()
-        .unwrap()
-        .into_iter()
+fn deserialize_precedence(value: &Value) -> Result<Precedence, DecisionEngineError> {
+    if value.is_null() {
+        return Ok(Precedence::default());
+    }
+    let order = match value.as_array() {
+        Some(order) => order,
+        None => {
+            return Err(DecisionEngineError::MalformedStrategy(String::from(
+                "Precedence must be an array of outcomes.",
+            )))
+        }
+    };
+    Precedence::new(
+        order
+            .iter()
+            .map(deserialize_eval_result)
+            .collect::<Result<Vec<EvalResult>, DecisionEngineError>>()?,
+    )
+}
+
+pub fn deserialize_module(value: &Value) -> Result<ModuleChildren, DecisionEngineError> {
+    if get_str(value, "module_type")? == "scorecard" {
+        return Ok(ModuleChildren::ScorecardModule(
+            deserialize_scorecard_module(value)?,
+        ));
+    }
+
+    let children = get_array(value, "children")?
+        .iter()
         .map(deserialize_module_children)
-        .collect();
-
-    let module = match value["module_type"].as_str().unwrap() {
-        "all" => PassAllModule::new(value["module_name"].as_str().unwrap().to_string(), children),
-        _ => panic!(format!(
-            "Unknown module_type: {}",
-            value["module_type"].as_str().unwrap()
-        )),
+        .collect::<Result<Vec<ModuleChildren>, DecisionEngineError>>()?;
+
+    let module_name = get_str(value, "module_name")?.to_string();
+    let precedence = deserialize_precedence(&value["precedence"])?;
+
+    let module = match get_str(value, "module_type")? {
+        "all" => {
+            ModuleChildren::PassAllModule(PassAllModule::new(module_name, precedence, children))
+        }
+        "any" => {
+            ModuleChildren::PassAnyModule(PassAnyModule::new(module_name, precedence, children))
+        }
+        "threshold" => {
+            let threshold = get_u64(value, "threshold")? as usize;
+            if threshold > children.len() {
+                return Err(DecisionEngineError::MalformedStrategy(format!(
+                    "Threshold {} can never be reached with {} children.",
+                    threshold,
+                    children.len()
+                )));
+            }
+            ModuleChildren::ThresholdModule(ThresholdModule::new(
+                module_name,
+                threshold,
+                precedence,
+                children,
+            ))
+        }
+        "first-match" => {
+            if !value["precedence"].is_null() {
+                return Err(DecisionEngineError::MalformedStrategy(String::from(
+                    "First-match modules return their first decided child and take no precedence.",
+                )));
+            }
+            ModuleChildren::FirstMatchModule(FirstMatchModule::new(
+                module_name,
+                deserialize_eval_result(&value["default"])?,
+                children,
+            ))
+        }
+        module_type => {
+            return Err(DecisionEngineError::MalformedStrategy(format!(
+                "Unknown module_type: {}",
+                module_type
+            )))
+        }
     };
 
-    module
+    Ok(module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Precedence;
+    use decisionengine::errors::DecisionEngineError;
+    use decisionengine::testing::{dataset, detailed, rule};
+    use decisionengine::{deserialize_strategy, EvalMode, EvalResult, Evaluatable};
+    use serde_json::Value;
+
+    fn module(module_type: &str, children: Vec<Value>) -> Value {
+        json!({
+            "type": "module",
+            "module_type": module_type,
+            "module_name": module_type,
+            "children": children,
+        })
+    }
+
+    /// Rule that always returns `result`, with reason code `RULE_<rule_id>`.
+    fn returns(rule_id: i64, result: &str) -> Value {
+        json!({
+            "type": "rule",
+            "rule_id": rule_id,
+            "rule_name": format!("Rule {}", rule_id),
+            "conditions": [{
+                "type": "condition",
+                "condition_id": "1",
+                "condition": "true",
+                "true": {
+                    "type": "return",
+                    "value": result,
+                    "reason_code": format!("RULE_{}", rule_id),
+                },
+                "false": {"type": "return", "value": result},
+            }],
+        })
+    }
+
+    /// Rule that fails to evaluate, as the default applicant has no income.
+    fn fails(rule_id: i64) -> Value {
+        rule(rule_id, Value::from("application_data_v1.income > 1000"))
+    }
+
+    fn decide(strategy: &Value) -> EvalResult {
+        let strategy = deserialize_strategy(strategy).unwrap();
+        strategy.eval(&mut dataset(json!({}))).unwrap()
+    }
+
+    #[test]
+    fn any_accepts_if_any_child_accepts() {
+        let strategy = module(
+            "any",
+            vec![returns(1, "REJECT"), returns(2, "ACCEPT"), fails(3)],
+        );
+        assert_eq!(decide(&strategy), EvalResult::Accept);
+        let strategy = module("any", vec![returns(1, "REJECT"), returns(2, "REJECT")]);
+        assert_eq!(decide(&strategy), EvalResult::Reject);
+    }
+
+    #[test]
+    fn threshold_accepts_once_enough_children_accept() {
+        let mut strategy = module(
+            "threshold",
+            vec![
+                returns(1, "ACCEPT"),
+                returns(2, "REJECT"),
+                returns(3, "ACCEPT"),
+                fails(4),
+            ],
+        );
+        strategy["threshold"] = json!(2);
+        assert_eq!(decide(&strategy), EvalResult::Accept);
+
+        strategy["threshold"] = json!(3);
+        strategy["children"][3] = returns(4, "REFER");
+        assert_eq!(decide(&strategy), EvalResult::Reject);
+
+        strategy["threshold"] = json!(0);
+        strategy["children"][0] = fails(1);
+        assert_eq!(decide(&strategy), EvalResult::Accept);
+    }
+
+    #[test]
+    fn threshold_stops_once_it_cannot_be_reached() {
+        let mut strategy = module(
+            "threshold",
+            vec![returns(1, "REJECT"), returns(2, "REFER"), fails(3)],
+        );
+        strategy["threshold"] = json!(2);
+        assert_eq!(decide(&strategy), EvalResult::Reject);
+
+        strategy["threshold"] = json!(3);
+        assert_eq!(decide(&strategy), EvalResult::Reject);
+    }
+
+    #[test]
+    fn unreachable_thresholds_are_rejected() {
+        let mut strategy = module("threshold", vec![returns(1, "ACCEPT")]);
+        strategy["threshold"] = json!(2);
+        match deserialize_strategy(&strategy) {
+            Err(DecisionEngineError::MalformedStrategy(message)) => {
+                assert_eq!(message, "Threshold 2 can never be reached with 1 children.")
+            }
+            _ => panic!("expected the strategy to be malformed"),
+        }
+    }
+
+    #[test]
+    fn first_match_with_precedence_is_rejected() {
+        let mut strategy = module("first-match", vec![returns(1, "ACCEPT")]);
+        strategy["default"] = json!("REFER");
+        assert!(deserialize_strategy(&strategy).is_ok());
+
+        strategy["precedence"] = json!(["ACCEPT", "REFER", "REJECT"]);
+        match deserialize_strategy(&strategy) {
+            Err(DecisionEngineError::MalformedStrategy(_)) => {}
+            _ => panic!("expected the strategy to be malformed"),
+        }
+    }
+
+    #[test]
+    fn first_match_returns_the_first_decisive_child() {
+        let mut strategy = module(
+            "first-match",
+            vec![
+                returns(1, "REFER"),
+                returns(2, "ACCEPT"),
+                returns(3, "REJECT"),
+                fails(4),
+            ],
+        );
+        strategy["default"] = json!("REFER");
+        assert_eq!(decide(&strategy), EvalResult::Accept);
+
+        strategy["children"] = json!([returns(1, "REFER")]);
+        assert_eq!(decide(&strategy), EvalResult::Refer);
+    }
+
+    #[test]
+    fn modules_serialize_their_own_fields() {
+        let mut strategy = module(
+            "threshold",
+            vec![returns(1, "ACCEPT"), {
+                let mut first_match = module("first-match", vec![returns(2, "REJECT")]);
+                first_match["default"] = json!("ACCEPT");
+                first_match
+            }],
+        );
+        strategy["threshold"] = json!(1);
+        let serialized = deserialize_strategy(&strategy).unwrap().serialize();
+        assert_eq!(serialized["threshold"], 1);
+        assert_eq!(serialized["children"][1]["module_type"], "first-match");
+        assert_eq!(serialized["children"][1]["default"], "ACCEPT");
+        assert_eq!(
+            deserialize_strategy(&serialized).unwrap().serialize(),
+            serialized
+        );
+    }
+
+    #[test]
+    fn refer_ranks_between_reject_and_accept() {
+        let strategy = module("all", vec![returns(1, "ACCEPT"), returns(2, "REFER")]);
+        assert_eq!(decide(&strategy), EvalResult::Refer);
+        let strategy = module(
+            "all",
+            vec![returns(1, "REFER"), returns(2, "REJECT"), fails(3)],
+        );
+        assert_eq!(decide(&strategy), EvalResult::Reject);
+        let strategy = module("any", vec![returns(1, "REJECT"), returns(2, "REFER")]);
+        assert_eq!(decide(&strategy), EvalResult::Refer);
+    }
+
+    #[test]
+    fn precedence_orders_outcomes() {
+        let mut strategy = module(
+            "all",
+            vec![returns(1, "REJECT"), returns(2, "ACCEPT"), fails(3)],
+        );
+        strategy["precedence"] = json!(["ACCEPT", "REFER", "REJECT"]);
+        assert_eq!(decide(&strategy), EvalResult::Accept);
+
+        strategy["module_type"] = json!("any");
+        strategy["children"] = json!([returns(1, "ACCEPT"), returns(2, "REFER")]);
+        assert_eq!(decide(&strategy), EvalResult::Refer);
+
+        strategy["module_type"] = json!("threshold");
+        strategy["threshold"] = json!(1);
+        strategy["children"] = json!([returns(1, "REJECT"), returns(2, "REFER")]);
+        assert_eq!(decide(&strategy), EvalResult::Refer);
+    }
+
+    #[test]
+    fn precedence_must_list_every_outcome_once() {
+        assert!(Precedence::new(vec![EvalResult::Accept, EvalResult::Reject]).is_err());
+        assert!(Precedence::new(vec![
+            EvalResult::Accept,
+            EvalResult::Accept,
+            EvalResult::Refer,
+            EvalResult::Reject,
+        ])
+        .is_err());
+
+        let mut strategy = module("all", vec![returns(1, "ACCEPT")]);
+        strategy["precedence"] = json!(["ACCEPT", "REJECT"]);
+        assert!(deserialize_strategy(&strategy).is_err());
+    }
+
+    #[test]
+    fn default_precedence_is_not_serialized() {
+        let mut strategy = module("all", vec![returns(1, "ACCEPT")]);
+        strategy["precedence"] = json!(["REJECT", "REFER", "ACCEPT"]);
+        let serialized = deserialize_strategy(&strategy).unwrap().serialize();
+        assert_eq!(serialized["precedence"], Value::Null);
+
+        strategy["precedence"] = json!(["ACCEPT", "REFER", "REJECT"]);
+        let serialized = deserialize_strategy(&strategy).unwrap().serialize();
+        assert_eq!(serialized["precedence"], strategy["precedence"]);
+    }
+
+    fn reason_codes(strategy: &Value) -> Vec<String> {
+        let strategy = deserialize_strategy(strategy).unwrap();
+        let mut input = dataset(json!({}));
+        strategy.eval(&mut input).unwrap();
+        input
+            .reasons()
+            .iter()
+            .map(|reason| reason.reason_code.clone())
+            .collect()
+    }
+
+    #[test]
+    fn reasons_support_the_outcome() {
+        let strategy = module(
+            "any",
+            vec![
+                returns(1, "REJECT"),
+                returns(2, "REFER"),
+                returns(3, "ACCEPT"),
+            ],
+        );
+        assert_eq!(reason_codes(&strategy), vec!["RULE_3"]);
+
+        let strategy = module(
+            "all",
+            vec![
+                returns(1, "ACCEPT"),
+                module("any", vec![returns(2, "REJECT"), returns(3, "REFER")]),
+                returns(4, "REFER"),
+            ],
+        );
+        assert_eq!(reason_codes(&strategy), vec!["RULE_3", "RULE_4"]);
+    }
+
+    #[test]
+    fn detailed_evaluation_keeps_the_same_reasons() {
+        let strategy = module(
+            "all",
+            vec![
+                module("any", vec![returns(1, "REJECT"), returns(2, "REFER")]),
+                returns(3, "REFER"),
+                returns(4, "ACCEPT"),
+            ],
+        );
+        assert_eq!(reason_codes(&strategy), vec!["RULE_2", "RULE_3"]);
+
+        let strategy = deserialize_strategy(&strategy).unwrap();
+        let mut input = dataset(json!({}));
+        strategy
+            .eval_detailed(&mut input, EvalMode::Exhaustive)
+            .unwrap();
+        let reason_codes: Vec<&str> = input
+            .reasons()
+            .iter()
+            .map(|reason| reason.reason_code.as_str())
+            .collect();
+        assert_eq!(reason_codes, vec!["RULE_2", "RULE_3"]);
+    }
+
+    #[test]
+    fn failures_after_the_decision_are_recorded() {
+        let strategy = module(
+            "all",
+            vec![
+                rule(1, Value::from("application_data_v1.age < 18")),
+                rule(2, Value::from("application_data_v1.income > 1000")),
+            ],
+        );
+        let details = detailed(&strategy, json!({}), EvalMode::ShortCircuit).unwrap();
+        assert_eq!(details["result"], "Reject");
+        assert_eq!(details["submodule_results"].as_array().unwrap().len(), 1);
+
+        let details = detailed(&strategy, json!({}), EvalMode::Exhaustive).unwrap();
+        assert_eq!(details["result"], "Reject");
+        assert_eq!(details["submodule_results"][1]["rule_id"], 2);
+        assert_eq!(
+            details["submodule_results"][1]["error"],
+            "Evaluation failed: Condition 1 evaluated to null and has no on_missing branch."
+        );
+    }
+
+    #[test]
+    fn failures_before_the_decision_fail_it() {
+        let strategy = module(
+            "all",
+            vec![
+                rule(1, Value::from("application_data_v1.income > 1000")),
+                rule(2, Value::from("application_data_v1.age < 18")),
+            ],
+        );
+        for mode in &[EvalMode::ShortCircuit, EvalMode::Exhaustive] {
+            assert!(detailed(&strategy, json!({}), *mode).is_err());
+        }
+    }
+
+    #[test]
+    fn detailed_evaluation_is_a_single_pass() {
+        let strategy = deserialize_strategy(&module(
+            "all",
+            vec![
+                module(
+                    "any",
+                    vec![
+                        rule(1, Value::from("application_data_v1.age >= 18")),
+                        rule(2, Value::from("application_data_v1.age >= 21")),
+                    ],
+                ),
+                rule(3, Value::from("application_data_v1.age < 75")),
+            ],
+        ))
+        .unwrap();
+
+        let mut input = dataset(json!({}));
+        strategy.eval(&mut input).unwrap();
+        assert_eq!(input.steps(), 2);
+
+        let mut input = dataset(json!({}));
+        let details = strategy
+            .eval_detailed(&mut input, EvalMode::ShortCircuit)
+            .unwrap();
+        assert_eq!(input.steps(), 2);
+        let details = serde_json::to_value(&details).unwrap();
+        assert_eq!(details["module_id"], "all");
+        assert_eq!(
+            details["submodule_results"][0]["submodule_results"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let mut input = dataset(json!({}));
+        let details = strategy
+            .eval_detailed(&mut input, EvalMode::Exhaustive)
+            .unwrap();
+        assert_eq!(input.steps(), 2);
+        let details = serde_json::to_value(&details).unwrap();
+        assert_eq!(
+            details["submodule_results"][0]["submodule_results"][1]["result"],
+            "Accept"
+        );
+    }
+
+    #[test]
+    fn steps_after_the_decision_do_not_use_the_budget() {
+        let strategy = deserialize_strategy(&module(
+            "all",
+            vec![
+                module(
+                    "any",
+                    vec![
+                        rule(1, Value::from("application_data_v1.age >= 18")),
+                        rule(2, Value::from("application_data_v1.age >= 21")),
+                        module(
+                            "all",
+                            vec![
+                                rule(3, Value::from("application_data_v1.age >= 25")),
+                                rule(4, Value::from("application_data_v1.age >= 30")),
+                            ],
+                        ),
+                    ],
+                ),
+                rule(5, Value::from("application_data_v1.age < 75")),
+            ],
+        ))
+        .unwrap();
+
+        let mut input = dataset(json!({})).with_step_budget(2);
+        assert_eq!(strategy.eval(&mut input).unwrap(), EvalResult::Accept);
+
+        let mut input = dataset(json!({})).with_step_budget(2);
+        let details = strategy
+            .eval_detailed(&mut input, EvalMode::Exhaustive)
+            .unwrap();
+        let details = serde_json::to_value(&details).unwrap();
+        assert_eq!(details["result"], "Accept");
+        let any = &details["submodule_results"][0]["submodule_results"];
+        assert_eq!(any[1]["result"], "Accept");
+        assert_eq!(any[2]["module_type"], "all");
+        assert_eq!(
+            any[2]["error"],
+            "Evaluation failed: step budget of 2 conditions exceeded."
+        );
+        assert_eq!(details["submodule_results"][1]["result"], "Accept");
+    }
 }
@@ -1,22 +1,168 @@
 use decisionengine::datasource::deserialize_input_node;
 use decisionengine::datasource::DecisionDataset;
+use decisionengine::deserializers::{get_array, get_str};
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::expressions::parse_expression;
 
 extern crate serde_json;
-use serde_json::Value;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Mutex;
 
 use decisionengine::operations::*;
+use decisionengine::types::{type_of, NodeType};
+use decisionengine::variables::{deserialize_variable_node, VariableTypes};
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum NodeResult {
-    Numeric(i32),
+    Numeric(Decimal),
     Boolean(bool),
     Text(String),
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
     Array(Vec<NodeResult>),
+    Record(BTreeMap<String, NodeResult>),
+    /// A value that is missing from the dataset. Operations propagate null
+    /// rather than failing, apart from `is_null`, `coalesce` and the
+    /// short-circuiting `&&` and `||`.
+    Null,
     Err(String),
 }
 
-pub trait EvalNode {
-    fn eval(&mut self, input: &mut DecisionDataset) -> NodeResult;
+impl NodeResult {
+    /// JSON form of the value, as shown in detailed decision output.
+    pub fn to_json(&self) -> Value {
+        match self {
+            NodeResult::Numeric(n) => decimal_to_json(n),
+            NodeResult::Boolean(b) => Value::Bool(*b),
+            NodeResult::Text(s) => Value::String(s.clone()),
+            NodeResult::Date(d) => Value::String(d.format("%Y-%m-%d").to_string()),
+            NodeResult::DateTime(d) => Value::String(d.to_rfc3339()),
+            NodeResult::Array(values) => {
+                Value::Array(values.iter().map(NodeResult::to_json).collect())
+            }
+            NodeResult::Record(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.to_json()))
+                    .collect(),
+            ),
+            NodeResult::Null => Value::Null,
+            NodeResult::Err(msg) => {
+                let mut error = serde_json::Map::new();
+                error.insert(String::from("error"), Value::String(msg.clone()));
+                Value::Object(error)
+            }
+        }
+    }
+}
+
+/// Writes a decimal as a JSON number. serde_json is built with
+/// `arbitrary_precision`, so the number keeps every digit of the decimal when
+/// written and read back rather than passing through a float.
+pub fn decimal_to_json(n: &Decimal) -> Value {
+    match n.to_i64().filter(|_| n.fract().is_zero()) {
+        Some(i) => Value::from(i),
+        None => Number::from_str(&n.to_string())
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+    }
+}
+
+pub trait EvalNode: Send + Sync {
+    fn eval(&self, input: &mut DecisionDataset) -> NodeResult;
+    /// Type of the values the node evaluates to, given the types of the
+    /// strategy's variables.
+    fn node_type(&self, variables: &VariableTypes) -> Result<NodeType, String>;
+    /// Canonical JSON form of the node, which deserializes to an equivalent
+    /// node.
+    fn serialize(&self) -> Value;
+
+    /// Evaluates the node for tracing, along with the values of the lvalue
+    /// and rvalue of a binary operation as the operation evaluated them.
+    /// Operands the operation did not evaluate, such as the rvalue of
+    /// `false && ...`, have no value.
+    fn eval_traced(&self, input: &mut DecisionDataset) -> TracedResult {
+        TracedResult {
+            value: self.eval(input),
+            lvalue: None,
+            rvalue: None,
+        }
+    }
+}
+
+pub struct TracedResult {
+    pub value: NodeResult,
+    pub lvalue: Option<NodeResult>,
+    pub rvalue: Option<NodeResult>,
+}
+
+/// Operand that keeps the value it was last evaluated to, so that operands
+/// can be traced without evaluating them a second time.
+struct RecordedNode<'a> {
+    node: &'a EvalNode,
+    value: Mutex<Option<NodeResult>>,
+}
+
+impl<'a> RecordedNode<'a> {
+    fn new(node: &'a EvalNode) -> Self {
+        RecordedNode {
+            node: node,
+            value: Mutex::new(None),
+        }
+    }
+
+    fn value(self) -> Option<NodeResult> {
+        self.value
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<'a> EvalNode for RecordedNode<'a> {
+    fn eval(&self, input: &mut DecisionDataset) -> NodeResult {
+        let value = self.node.eval(input);
+        *self
+            .value
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(value.clone());
+        value
+    }
+
+    fn node_type(&self, variables: &VariableTypes) -> Result<NodeType, String> {
+        self.node.node_type(variables)
+    }
+
+    fn serialize(&self) -> Value {
+        self.node.serialize()
+    }
+}
+
+fn node_json(node_type: &str) -> Map<String, Value> {
+    let mut node = Map::new();
+    node.insert(String::from("type"), Value::from(node_type));
+    node
+}
+
+fn op_json(op: &str) -> Map<String, Value> {
+    let mut node = node_json("op");
+    node.insert(String::from("op"), Value::from(op));
+    node
+}
+
+/// The `value_type` a constant needs to be read back as the given value, if
+/// it is or holds dates or datetimes.
+fn constant_value_type(value: &NodeResult) -> Option<&'static str> {
+    match value {
+        NodeResult::Date(_) => Some("date"),
+        NodeResult::DateTime(_) => Some("datetime"),
+        NodeResult::Array(values) => values.iter().filter_map(constant_value_type).next(),
+        _ => None,
+    }
 }
 
 struct ConstantRootNode {
@@ -24,109 +170,561 @@ struct ConstantRootNode {
 }
 
 impl EvalNode for ConstantRootNode {
-    fn eval(&mut self, _: &mut DecisionDataset) -> NodeResult {
+    fn eval(&self, _: &mut DecisionDataset) -> NodeResult {
         match &self.value {
             &NodeResult::Boolean(b) => NodeResult::Boolean(b),
             &NodeResult::Numeric(n) => NodeResult::Numeric(n),
             &NodeResult::Text(ref s) => NodeResult::Text(s.clone()),
+            &NodeResult::Date(d) => NodeResult::Date(d),
+            &NodeResult::DateTime(d) => NodeResult::DateTime(d),
             &NodeResult::Array(ref a) => NodeResult::Array(a.clone()),
+            &NodeResult::Record(ref r) => NodeResult::Record(r.clone()),
+            NodeResult::Null => NodeResult::Null,
             NodeResult::Err(msg) => NodeResult::Err(msg.clone()),
         }
     }
+
+    fn node_type(&self, _variables: &VariableTypes) -> Result<NodeType, String> {
+        type_of(&self.value)
+    }
+
+    fn serialize(&self) -> Value {
+        let mut node = node_json("constant");
+        node.insert(String::from("value"), self.value.to_json());
+        if let Some(value_type) = constant_value_type(&self.value) {
+            node.insert(String::from("value_type"), Value::from(value_type));
+        }
+        Value::Object(node)
+    }
 }
 
 struct BinOpNode {
+    op: String,
     lvalue: Box<EvalNode>,
     rvalue: Box<EvalNode>,
     operation: Box<BinaryOperation>,
 }
 
 impl EvalNode for BinOpNode {
-    fn eval(&mut self, input: &mut DecisionDataset) -> NodeResult {
+    fn eval(&self, input: &mut DecisionDataset) -> NodeResult {
+        self.operation.eval(&*self.lvalue, &*self.rvalue, input)
+    }
+
+    fn node_type(&self, variables: &VariableTypes) -> Result<NodeType, String> {
+        let lvalue = self.lvalue.node_type(variables)?;
+        let rvalue = self.rvalue.node_type(variables)?;
+        self.operation.result_type(&lvalue, &rvalue)
+    }
+
+    fn eval_traced(&self, input: &mut DecisionDataset) -> TracedResult {
+        let lvalue = RecordedNode::new(&*self.lvalue);
+        // The rvalue of an array operation only has a value per element.
+        if self.operation.binds_element() {
+            let value = self.operation.eval(&lvalue, &*self.rvalue, input);
+            return TracedResult {
+                value: value,
+                lvalue: lvalue.value(),
+                rvalue: None,
+            };
+        }
+        let rvalue = RecordedNode::new(&*self.rvalue);
+        let value = self.operation.eval(&lvalue, &rvalue, input);
+        TracedResult {
+            value: value,
+            lvalue: lvalue.value(),
+            rvalue: rvalue.value(),
+        }
+    }
+
+    fn serialize(&self) -> Value {
+        let mut node = op_json(&self.op);
+        node.insert(String::from("lvalue"), self.lvalue.serialize());
+        node.insert(String::from("rvalue"), self.rvalue.serialize());
+        Value::Object(node)
+    }
+}
+
+struct UnaryOpNode {
+    op: String,
+    value: Box<EvalNode>,
+    operation: Box<UnaryOperation>,
+}
+
+impl EvalNode for UnaryOpNode {
+    fn eval(&self, input: &mut DecisionDataset) -> NodeResult {
+        self.operation.eval(&*self.value, input)
+    }
+
+    fn node_type(&self, variables: &VariableTypes) -> Result<NodeType, String> {
         self.operation
-            .eval(&mut self.lvalue, &mut self.rvalue, input)
+            .result_type(&self.value.node_type(variables)?)
+    }
+
+    fn serialize(&self) -> Value {
+        let mut node = op_json(&self.op);
+        node.insert(String::from("value"), self.value.serialize());
+        Value::Object(node)
     }
 }
 
-pub fn deserialize_node(v: &Value) -> (Box<EvalNode>, bool) {
-    let node_type = v["type"].as_str().unwrap();
-    if node_type == "constant" {
-        deserialize_const_node(v)
-    } else if node_type == "input" {
-        deserialize_input_node(v["value"].as_str().unwrap())
-    } else {
-        match v["type"].as_str().unwrap() {
-            "op" => match v["op"].as_str().unwrap() {
-                "pow" => deserialize_bin_op_node(v, Box::new(PowerOperation {})),
-                ">=" => deserialize_bin_op_node(v, Box::new(GreaterThanOrEqualsOperation {})),
-                "<=" => deserialize_bin_op_node(v, Box::new(LessThanOrEqualsOperation {})),
-                "&&" => deserialize_bin_op_node(v, Box::new(AndOperation {})),
-                "+" => deserialize_bin_op_node(v, Box::new(AdditionOperation {})),
-                "==" => deserialize_bin_op_node(v, Box::new(EqualsOperation {})),
-                "array_contains" => deserialize_bin_op_node(v, Box::new(ArrayContainsOperation {})),
-                "regex_contains" => deserialize_bin_op_node(v, Box::new(RegexContainsOperation {})),
-                _ => panic!(format!(
-                    "Cannot deserialize: unknown operation {}",
-                    v["op"].to_string()
-                )),
-            },
-            _ => panic!(format!(
-                "Cannot deserialize node type: {}",
-                v["type"].to_string()
+struct TernaryOpNode {
+    op: String,
+    values: [Box<EvalNode>; 3],
+    operation: Box<TernaryOperation>,
+}
+
+impl EvalNode for TernaryOpNode {
+    fn eval(&self, input: &mut DecisionDataset) -> NodeResult {
+        self.operation.eval(&self.values, input)
+    }
+
+    fn node_type(&self, variables: &VariableTypes) -> Result<NodeType, String> {
+        self.operation.result_type(&[
+            self.values[0].node_type(variables)?,
+            self.values[1].node_type(variables)?,
+            self.values[2].node_type(variables)?,
+        ])
+    }
+
+    fn serialize(&self) -> Value {
+        let mut node = op_json(&self.op);
+        let args = self.values.iter().map(|value| value.serialize()).collect();
+        node.insert(String::from("args"), Value::Array(args));
+        Value::Object(node)
+    }
+}
+
+/// The array element currently bound by an enclosing array operation such as
+/// `any` or `map`, or one of its fields if the element is a record.
+struct ElementNode {
+    field: Option<String>,
+}
+
+impl EvalNode for ElementNode {
+    fn eval(&self, input: &mut DecisionDataset) -> NodeResult {
+        match (input.element(), &self.field) {
+            (None, _) => NodeResult::Err(String::from(
+                "Element accessed outside of an array operation.",
+            )),
+            (Some(element), &None) => element.clone(),
+            (Some(&NodeResult::Record(ref fields)), &Some(ref field)) => {
+                fields.get(field).cloned().unwrap_or(NodeResult::Null)
+            }
+            (Some(&NodeResult::Null), &Some(_)) => NodeResult::Null,
+            (Some(_), &Some(ref field)) => NodeResult::Err(format!(
+                "Cannot access field {} of an element that is not a record.",
+                field
             )),
         }
     }
+
+    fn node_type(&self, _variables: &VariableTypes) -> Result<NodeType, String> {
+        Ok(NodeType::Any)
+    }
+
+    fn serialize(&self) -> Value {
+        let mut node = node_json("element");
+        if let Some(ref field) = self.field {
+            node.insert(String::from("value"), Value::from(field.as_str()));
+        }
+        Value::Object(node)
+    }
 }
 
-fn deserialize_bin_op_node(v: &Value, op: Box<BinaryOperation>) -> (Box<EvalNode>, bool) {
-    let (mut lvalue, lconst) = deserialize_node(&v["lvalue"]);
-    let (mut rvalue, rconst) = deserialize_node(&v["rvalue"]);
-    if lconst && rconst {
-        (
-            Box::new(ConstantRootNode {
-                value: op.eval(&mut lvalue, &mut rvalue, &mut DecisionDataset::get_empty()),
+/// Current time, taken from the clock of the dataset being evaluated.
+struct NowNode {}
+
+impl EvalNode for NowNode {
+    fn eval(&self, input: &mut DecisionDataset) -> NodeResult {
+        NodeResult::DateTime(input.now())
+    }
+
+    fn node_type(&self, _variables: &VariableTypes) -> Result<NodeType, String> {
+        Ok(NodeType::DateTime)
+    }
+
+    fn serialize(&self) -> Value {
+        Value::Object(node_json("now"))
+    }
+}
+
+/// Deserializes a node from its JSON form, or from an expression string in
+/// the syntax read by `parse_expression`.
+pub fn deserialize_node(v: &Value) -> Result<(Box<EvalNode>, bool), DecisionEngineError> {
+    if let Some(source) = v.as_str() {
+        let parsed = parse_expression(source).map_err(DecisionEngineError::MalformedStrategy)?;
+        return deserialize_node(&parsed);
+    }
+    match get_str(v, "type")? {
+        "constant" => deserialize_const_node(v),
+        "input" => deserialize_input_node(get_str(v, "value")?),
+        "now" => Ok((Box::new(NowNode {}), false)),
+        "variable" => deserialize_variable_node(get_str(v, "value")?),
+        "element" => Ok((
+            Box::new(ElementNode {
+                field: v["value"].as_str().map(String::from),
             }),
-            true,
-        )
+            false,
+        )),
+        "op" => {
+            let op = get_str(v, "op")?;
+            let node = match operation_arity(v) {
+                Arity::Unary => unary_operation(op).map(|op| deserialize_unary_op_node(v, op)),
+                Arity::Binary => binary_operation(op).map(|op| deserialize_bin_op_node(v, op)),
+                Arity::Ternary => {
+                    ternary_operation(op).map(|op| deserialize_ternary_op_node(v, op))
+                }
+            };
+            node.unwrap_or_else(|| Err(DecisionEngineError::MalformedStrategy(operation_error(v))))
+        }
+        _ => Err(DecisionEngineError::MalformedStrategy(format!(
+            "Cannot deserialize node type: {}",
+            v["type"].to_string()
+        ))),
+    }
+}
+
+/// Number of operands of an operation node, given by how they are written:
+/// `"value"` for unary, `"lvalue"` and `"rvalue"` for binary and `"args"` for
+/// ternary operations.
+pub enum Arity {
+    Unary,
+    Binary,
+    Ternary,
+}
+
+pub fn operation_arity(v: &Value) -> Arity {
+    if !v["args"].is_null() {
+        Arity::Ternary
+    } else if !v["value"].is_null() {
+        Arity::Unary
     } else {
-        (
+        Arity::Binary
+    }
+}
+
+/// Explains why the operation of a node could not be found.
+pub fn operation_error(v: &Value) -> String {
+    let op = v["op"].as_str().unwrap_or_default();
+    let arity = match operation_arity(v) {
+        _ if binary_operation(op).is_none()
+            && unary_operation(op).is_none()
+            && ternary_operation(op).is_none() =>
+        {
+            return format!(
+                "Cannot deserialize: unknown operation {}",
+                v["op"].to_string()
+            )
+        }
+        Arity::Unary => "a \"value\" operand",
+        Arity::Binary => "\"lvalue\" and \"rvalue\" operands",
+        Arity::Ternary => "\"args\"",
+    };
+    format!("Operation {} does not take {}.", op, arity)
+}
+
+pub fn binary_operation(op: &str) -> Option<Box<BinaryOperation>> {
+    match op {
+        "pow" => Some(Box::new(PowerOperation {})),
+        ">=" => Some(Box::new(GreaterThanOrEqualsOperation {})),
+        "<=" => Some(Box::new(LessThanOrEqualsOperation {})),
+        ">" => Some(Box::new(GreaterThanOperation {})),
+        "<" => Some(Box::new(LessThanOperation {})),
+        "&&" => Some(Box::new(AndOperation {})),
+        "||" => Some(Box::new(OrOperation {})),
+        "+" => Some(Box::new(AdditionOperation {})),
+        "-" => Some(Box::new(SubtractionOperation {})),
+        "*" => Some(Box::new(MultiplicationOperation {})),
+        "/" => Some(Box::new(DivisionOperation {})),
+        "%" => Some(Box::new(RemainderOperation {})),
+        "min" => Some(Box::new(MinOperation {})),
+        "max" => Some(Box::new(MaxOperation {})),
+        "years_between" => Some(Box::new(YearsBetweenOperation {})),
+        "days_between" => Some(Box::new(DaysBetweenOperation {})),
+        "add_days" => Some(Box::new(AddDaysOperation {})),
+        "coalesce" => Some(Box::new(CoalesceOperation {})),
+        "==" => Some(Box::new(EqualsOperation {})),
+        "!=" => Some(Box::new(NotEqualsOperation {})),
+        "array_contains" => Some(Box::new(ArrayContainsOperation {})),
+        "regex_contains" => Some(Box::new(RegexContainsOperation { regex: None })),
+        "starts_with" => Some(Box::new(StartsWithOperation {})),
+        "ends_with" => Some(Box::new(EndsWithOperation {})),
+        "concat" => Some(Box::new(ConcatOperation {})),
+        "normalize" => Some(Box::new(NormalizeOperation {})),
+        "any" => Some(Box::new(AnyOperation {})),
+        "all" => Some(Box::new(AllOperation {})),
+        "count" => Some(Box::new(CountOperation {})),
+        "filter" => Some(Box::new(FilterOperation {})),
+        "map" => Some(Box::new(MapOperation {})),
+        _ => None,
+    }
+}
+
+pub fn unary_operation(op: &str) -> Option<Box<UnaryOperation>> {
+    match op {
+        "!" => Some(Box::new(NotOperation {})),
+        "neg" => Some(Box::new(NegateOperation {})),
+        "abs" => Some(Box::new(AbsOperation {})),
+        "date" => Some(Box::new(DateOperation {})),
+        "is_null" => Some(Box::new(IsNullOperation {})),
+        "lower" => Some(Box::new(LowerOperation {})),
+        "upper" => Some(Box::new(UpperOperation {})),
+        "trim" => Some(Box::new(TrimOperation {})),
+        "length" => Some(Box::new(LengthOperation {})),
+        "sum" => Some(Box::new(SumOperation {})),
+        "min" => Some(Box::new(ArrayMinOperation {})),
+        "max" => Some(Box::new(ArrayMaxOperation {})),
+        _ => None,
+    }
+}
+
+/// Operations taking three operands, given as the node's `"args"` array.
+pub fn ternary_operation(op: &str) -> Option<Box<TernaryOperation>> {
+    match op {
+        "substring" => Some(Box::new(SubstringOperation {})),
+        _ => None,
+    }
+}
+
+fn deserialize_bin_op_node(
+    v: &Value,
+    mut op: Box<BinaryOperation>,
+) -> Result<(Box<EvalNode>, bool), DecisionEngineError> {
+    let (lvalue, lconst) = deserialize_node(&v["lvalue"])?;
+    let (rvalue, rconst) = deserialize_node(&v["rvalue"])?;
+    if rconst {
+        op.bind_constant_rvalue(&rvalue.eval(&mut DecisionDataset::get_empty()))
+            .map_err(DecisionEngineError::MalformedStrategy)?;
+    }
+    if lconst && rconst {
+        match op.eval(&*lvalue, &*rvalue, &mut DecisionDataset::get_empty()) {
+            NodeResult::Err(msg) => Err(DecisionEngineError::MalformedStrategy(msg)),
+            value => Ok((Box::new(ConstantRootNode { value: value }), true)),
+        }
+    } else {
+        Ok((
             Box::new(BinOpNode {
+                op: get_str(v, "op")?.to_string(),
                 lvalue: lvalue,
                 rvalue: rvalue,
                 operation: op,
             }),
             false,
-        )
+        ))
     }
 }
 
-fn deserialize_const_node_value(v: &Value) -> NodeResult {
-    if v.is_array() {
-        let array_value: Vec<NodeResult> = v.as_array()
-            .unwrap()
-            .iter()
-            .map(|v| deserialize_const_node_value(v))
-            .collect();
-        return NodeResult::Array(array_value);
+fn deserialize_unary_op_node(
+    v: &Value,
+    op: Box<UnaryOperation>,
+) -> Result<(Box<EvalNode>, bool), DecisionEngineError> {
+    let (value, is_const) = deserialize_node(&v["value"])?;
+    if is_const {
+        match op.eval(&*value, &mut DecisionDataset::get_empty()) {
+            NodeResult::Err(msg) => Err(DecisionEngineError::MalformedStrategy(msg)),
+            value => Ok((Box::new(ConstantRootNode { value: value }), true)),
+        }
+    } else {
+        Ok((
+            Box::new(UnaryOpNode {
+                op: get_str(v, "op")?.to_string(),
+                value: value,
+                operation: op,
+            }),
+            false,
+        ))
     }
-    if v.is_boolean() {
-        return NodeResult::Boolean(v.as_bool().unwrap());
+}
+
+fn deserialize_ternary_op_node(
+    v: &Value,
+    op: Box<TernaryOperation>,
+) -> Result<(Box<EvalNode>, bool), DecisionEngineError> {
+    let args = get_array(v, "args")?;
+    if args.len() != 3 {
+        return Err(DecisionEngineError::MalformedStrategy(format!(
+            "Operation {} expects 3 args, got {}.",
+            get_str(v, "op")?,
+            args.len()
+        )));
+    }
+    let (first, first_const) = deserialize_node(&args[0])?;
+    let (second, second_const) = deserialize_node(&args[1])?;
+    let (third, third_const) = deserialize_node(&args[2])?;
+    let values = [first, second, third];
+    if first_const && second_const && third_const {
+        match op.eval(&values, &mut DecisionDataset::get_empty()) {
+            NodeResult::Err(msg) => Err(DecisionEngineError::MalformedStrategy(msg)),
+            value => Ok((Box::new(ConstantRootNode { value: value }), true)),
+        }
+    } else {
+        Ok((
+            Box::new(TernaryOpNode {
+                op: get_str(v, "op")?.to_string(),
+                values: values,
+                operation: op,
+            }),
+            false,
+        ))
+    }
+}
+
+/// Parses an ISO 8601 calendar date such as `2018-07-31`.
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+/// Parses an ISO 8601 date and time with an offset, such as
+/// `2018-07-31T12:00:00Z`, normalised to UTC.
+pub fn parse_datetime(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+/// Reads a JSON number as a decimal. Numbers are parsed from their textual
+/// form, so `0.1` is exactly one tenth rather than the nearest float.
+pub fn parse_decimal(n: &Number) -> Option<Decimal> {
+    if let Some(i) = n.as_i64() {
+        return Some(Decimal::from(i));
+    }
+    let s = n.to_string();
+    Decimal::from_str(&s)
+        .or_else(|_| Decimal::from_scientific(&s))
+        .ok()
+}
+
+/// Deserializes a constant. Strings are read as dates or datetimes if the
+/// constant node has a `value_type` of `"date"` or `"datetime"`.
+fn deserialize_const_node_value(
+    v: &Value,
+    value_type: Option<&str>,
+) -> Result<NodeResult, DecisionEngineError> {
+    if v.is_null() {
+        return Ok(NodeResult::Null);
+    }
+    if let Some(values) = v.as_array() {
+        let mut array_value: Vec<NodeResult> = Vec::new();
+        for v in values {
+            array_value.push(deserialize_const_node_value(v, value_type)?);
+        }
+        return Ok(NodeResult::Array(array_value));
     }
-    if v.is_string() {
-        return NodeResult::Text(v.as_str().unwrap().to_string());
+    if let Some(b) = v.as_bool() {
+        return Ok(NodeResult::Boolean(b));
     }
-    if v.is_i64() {
-        return NodeResult::Numeric(v.as_i64().unwrap() as i32);
+    if let Some(s) = v.as_str() {
+        return match value_type {
+            None => Ok(NodeResult::Text(s.to_string())),
+            Some("date") => parse_date(s).map(NodeResult::Date).ok_or_else(|| {
+                DecisionEngineError::MalformedStrategy(format!("Invalid ISO 8601 date {}.", s))
+            }),
+            Some("datetime") => parse_datetime(s).map(NodeResult::DateTime).ok_or_else(|| {
+                DecisionEngineError::MalformedStrategy(format!("Invalid ISO 8601 datetime {}.", s))
+            }),
+            Some(value_type) => Err(DecisionEngineError::MalformedStrategy(format!(
+                "Unknown constant value_type {}.",
+                value_type
+            ))),
+        };
     }
-    panic!(format!(
+    if let Value::Number(n) = v {
+        if let Some(n) = parse_decimal(n) {
+            return Ok(NodeResult::Numeric(n));
+        }
+    }
+    Err(DecisionEngineError::MalformedStrategy(format!(
         "Can't deserialize constant input: {}",
         v.to_string()
-    ));
+    )))
 }
 
-fn deserialize_const_node(v: &Value) -> (Box<EvalNode>, bool) {
+/// Deserializes a constant node. A `"value"` of `null` is the null constant,
+/// but the key must be present.
+fn deserialize_const_node(v: &Value) -> Result<(Box<EvalNode>, bool), DecisionEngineError> {
+    if v.get("value").is_none() {
+        return Err(DecisionEngineError::MalformedStrategy(String::from(
+            "Can't deserialize constant input: null",
+        )));
+    }
     let root = ConstantRootNode {
-        value: deserialize_const_node_value(&v["value"]),
+        value: deserialize_const_node_value(&v["value"], v["value_type"].as_str())?,
     };
-    (Box::new(root), true)
+    Ok((Box::new(root), true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decisionengine::testing::{dataset, eval, eval_with};
+
+    fn number(source: &str) -> Number {
+        match serde_json::from_str(source).unwrap() {
+            Value::Number(n) => n,
+            _ => panic!("{} is not a number", source),
+        }
+    }
+
+    #[test]
+    fn decimals_are_exact() {
+        assert_eq!(
+            eval(Value::from("0.1 + 0.2 == 0.3")),
+            NodeResult::Boolean(true)
+        );
+        assert_eq!(
+            parse_decimal(&number("0.1")),
+            Some(Decimal::from_str("0.1").unwrap())
+        );
+        assert_eq!(parse_decimal(&number("1e3")), Some(Decimal::from(1000)));
+    }
+
+    #[test]
+    fn integers_keep_all_64_bits() {
+        let big = number("9007199254740993");
+        assert_eq!(
+            parse_decimal(&big),
+            Some(Decimal::from(9007199254740993i64))
+        );
+        assert_eq!(
+            decimal_to_json(&parse_decimal(&big).unwrap()),
+            Value::from(9007199254740993i64)
+        );
+
+        let mut input = dataset(json!({ "income": Value::Number(big) }));
+        assert_eq!(
+            eval_with(
+                Value::from("application_data_v1.income == 9007199254740992"),
+                &mut input
+            ),
+            NodeResult::Boolean(false)
+        );
+        assert_eq!(
+            eval_with(
+                Value::from("application_data_v1.income - 9007199254740992"),
+                &mut input
+            ),
+            NodeResult::Numeric(Decimal::from(1))
+        );
+    }
+
+    #[test]
+    fn decimals_serialize_as_numbers() {
+        assert_eq!(decimal_to_json(&Decimal::from(42)), json!(42));
+        assert_eq!(
+            decimal_to_json(&Decimal::from_str("2.50").unwrap()).to_string(),
+            "2.50"
+        );
+        assert_eq!(
+            NodeResult::Numeric(Decimal::from_str("-0.125").unwrap()).to_json(),
+            Value::Number(number("-0.125"))
+        );
+    }
+
+    #[test]
+    fn decimal_overflow_is_an_error() {
+        assert_eq!(
+            eval(Value::from("application_data_v1.age * pow(10, 28)")),
+            NodeResult::Err(String::from("Operation * overflowed."))
+        );
+    }
 }
@@ -1,216 +1,1762 @@
 extern crate regex;
 
 use self::regex::Regex;
+use chrono::{Datelike, Duration, NaiveDate};
 use decisionengine::datasource::DecisionDataset;
 use decisionengine::nodes::{EvalNode, NodeResult};
+use decisionengine::types::{
+    check_operand, check_operands, operand_error, operands_error, type_of, NodeType,
+};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+use unicode_normalization::UnicodeNormalization;
 
-pub trait BinaryOperation {
-    fn eval(
-        &self,
-        lnode: &mut Box<EvalNode>,
-        rnode: &mut Box<EvalNode>,
-        inputs: &mut DecisionDataset,
-    ) -> NodeResult;
+pub trait BinaryOperation: Send + Sync {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult;
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String>;
+
+    /// Called once when the strategy loads if the rvalue is a constant, so
+    /// the operation can prepare anything derived from it ahead of time.
+    fn bind_constant_rvalue(&mut self, _rvalue: &NodeResult) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Whether the rvalue is evaluated once per element of the lvalue array,
+    /// with the element available to it through `element` nodes.
+    fn binds_element(&self) -> bool {
+        false
+    }
+}
+
+pub trait UnaryOperation: Send + Sync {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult;
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String>;
+}
+
+pub trait TernaryOperation: Send + Sync {
+    fn eval(&self, nodes: &[Box<EvalNode>; 3], inputs: &mut DecisionDataset) -> NodeResult;
+
+    fn result_type(&self, values: &[NodeType; 3]) -> Result<NodeType, String>;
+}
+
+/// Builds the error for operands that do not match `expected`, passing
+/// through any error the operands evaluated to.
+fn operands_type_error(
+    op: &str,
+    expected: &NodeType,
+    lvalue: &NodeResult,
+    rvalue: &NodeResult,
+) -> NodeResult {
+    match (type_of(lvalue), type_of(rvalue)) {
+        (Err(msg), _) | (_, Err(msg)) => NodeResult::Err(msg),
+        (Ok(l), Ok(r)) => NodeResult::Err(operands_error(op, expected, &l, &r)),
+    }
+}
+
+fn operand_type_error(op: &str, expected: &NodeType, value: &NodeResult) -> NodeResult {
+    match type_of(value) {
+        Err(msg) => NodeResult::Err(msg),
+        Ok(t) => NodeResult::Err(operand_error(op, expected, &t)),
+    }
+}
+
+/// Evaluates both operands, returning early with the first error or, if
+/// either operand is null, with null.
+fn eval_operands(
+    lnode: &EvalNode,
+    rnode: &EvalNode,
+    inputs: &mut DecisionDataset,
+) -> Result<(NodeResult, NodeResult), NodeResult> {
+    let l = lnode.eval(inputs);
+    if let NodeResult::Err(_) = l {
+        return Err(l);
+    }
+    match (l, rnode.eval(inputs)) {
+        (_, e @ NodeResult::Err(_)) => Err(e),
+        (NodeResult::Null, _) | (_, NodeResult::Null) => Err(NodeResult::Null),
+        (l, r) => Ok((l, r)),
+    }
+}
+
+fn numeric_operands(
+    op: &str,
+    lnode: &EvalNode,
+    rnode: &EvalNode,
+    inputs: &mut DecisionDataset,
+) -> Result<(Decimal, Decimal), NodeResult> {
+    match eval_operands(lnode, rnode, inputs)? {
+        (NodeResult::Numeric(l), NodeResult::Numeric(r)) => Ok((l, r)),
+        (l, r) => Err(operands_type_error(op, &NodeType::Numeric, &l, &r)),
+    }
+}
+
+fn checked(op: &str, value: Option<Decimal>) -> NodeResult {
+    match value {
+        Some(n) => NodeResult::Numeric(n),
+        None => NodeResult::Err(format!("Operation {} overflowed.", op)),
+    }
+}
+
+/// Raises `base` to `exponent` by repeated squaring, returning `None` on
+/// overflow.
+fn power(base: Decimal, exponent: u64) -> Option<Decimal> {
+    let mut result = Decimal::from(1);
+    let mut base = base;
+    let mut exponent = exponent;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.checked_mul(base)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = base.checked_mul(base)?;
+        }
+    }
+    Some(result)
+}
+
+/// Type that decides how two operands are compared: the left one, unless
+/// only the right one is known.
+fn operand_type<'a>(lvalue: &'a NodeType, rvalue: &'a NodeType) -> &'a NodeType {
+    match lvalue {
+        NodeType::Any => rvalue,
+        _ => lvalue,
+    }
+}
+
+/// Ordering operations compare numbers, dates or datetimes, with both
+/// operands of the same type.
+fn comparison_type(op: &str, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+    match operand_type(lvalue, rvalue) {
+        NodeType::Date => check_operands(op, lvalue, rvalue, NodeType::Date, NodeType::Boolean),
+        NodeType::DateTime => {
+            check_operands(op, lvalue, rvalue, NodeType::DateTime, NodeType::Boolean)
+        }
+        _ => check_operands(op, lvalue, rvalue, NodeType::Numeric, NodeType::Boolean),
+    }
+}
+
+fn compare(
+    op: &str,
+    lnode: &EvalNode,
+    rnode: &EvalNode,
+    inputs: &mut DecisionDataset,
+) -> Result<Ordering, NodeResult> {
+    match eval_operands(lnode, rnode, inputs)? {
+        (NodeResult::Numeric(l), NodeResult::Numeric(r)) => Ok(l.cmp(&r)),
+        (NodeResult::Date(l), NodeResult::Date(r)) => Ok(l.cmp(&r)),
+        (NodeResult::DateTime(l), NodeResult::DateTime(r)) => Ok(l.cmp(&r)),
+        (l, r) => match (type_of(&l), type_of(&r)) {
+            (Err(msg), _) | (_, Err(msg)) => Err(NodeResult::Err(msg)),
+            (Ok(ltype), Ok(rtype)) => Err(NodeResult::Err(
+                comparison_type(op, &ltype, &rtype)
+                    .err()
+                    .unwrap_or_else(|| operands_error(op, &NodeType::Numeric, &ltype, &rtype)),
+            )),
+        },
+    }
+}
+
+fn date_operands(
+    op: &str,
+    lnode: &EvalNode,
+    rnode: &EvalNode,
+    inputs: &mut DecisionDataset,
+) -> Result<(NaiveDate, NaiveDate), NodeResult> {
+    match eval_operands(lnode, rnode, inputs)? {
+        (NodeResult::Date(l), NodeResult::Date(r)) => Ok((l, r)),
+        (l, r) => Err(operands_type_error(op, &NodeType::Date, &l, &r)),
+    }
+}
+
+fn text_operands(
+    op: &str,
+    lnode: &EvalNode,
+    rnode: &EvalNode,
+    inputs: &mut DecisionDataset,
+) -> Result<(String, String), NodeResult> {
+    match eval_operands(lnode, rnode, inputs)? {
+        (NodeResult::Text(l), NodeResult::Text(r)) => Ok((l, r)),
+        (l, r) => Err(operands_type_error(op, &NodeType::Text, &l, &r)),
+    }
+}
+
+fn text_operand(
+    op: &str,
+    node: &EvalNode,
+    inputs: &mut DecisionDataset,
+) -> Result<String, NodeResult> {
+    match node.eval(inputs) {
+        NodeResult::Text(t) => Ok(t),
+        NodeResult::Null => Err(NodeResult::Null),
+        v => Err(operand_type_error(op, &NodeType::Text, &v)),
+    }
+}
+
+fn equality_type(op: &str, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+    match operand_type(lvalue, rvalue) {
+        NodeType::Date => check_operands(op, lvalue, rvalue, NodeType::Date, NodeType::Boolean),
+        NodeType::DateTime => {
+            check_operands(op, lvalue, rvalue, NodeType::DateTime, NodeType::Boolean)
+        }
+        NodeType::Boolean => {
+            check_operands(op, lvalue, rvalue, NodeType::Boolean, NodeType::Boolean)
+        }
+        NodeType::Text => check_operands(op, lvalue, rvalue, NodeType::Text, NodeType::Boolean),
+        _ => check_operands(op, lvalue, rvalue, NodeType::Numeric, NodeType::Boolean),
+    }
+}
+
+/// Evaluates both operands of `==` or `!=` and checks that they are
+/// comparable, returning whether they are equal.
+fn equals(
+    op: &str,
+    lnode: &EvalNode,
+    rnode: &EvalNode,
+    inputs: &mut DecisionDataset,
+) -> Result<bool, NodeResult> {
+    let (l, r) = eval_operands(lnode, rnode, inputs)?;
+    match (type_of(&l), type_of(&r)) {
+        (Err(msg), _) | (_, Err(msg)) => Err(NodeResult::Err(msg)),
+        (Ok(ltype), Ok(rtype)) => match equality_type(op, &ltype, &rtype) {
+            Ok(_) => Ok(l == r),
+            Err(msg) => Err(NodeResult::Err(msg)),
+        },
+    }
 }
 
 pub struct AdditionOperation {}
 
 impl BinaryOperation for AdditionOperation {
-    fn eval(
-        &self,
-        lnode: &mut Box<EvalNode>,
-        rnode: &mut Box<EvalNode>,
-        inputs: &mut DecisionDataset,
-    ) -> NodeResult {
-        match lnode.eval(inputs) {
-            NodeResult::Numeric(l) => match rnode.eval(inputs) {
-                NodeResult::Numeric(r) => NodeResult::Numeric(l + r),
-                NodeResult::Boolean(_) => NodeResult::Err(String::from(
-                    "Expected int, got boolean during addition operation.",
-                )),
-                e => e,
-            },
-            NodeResult::Boolean(_) => NodeResult::Err(String::from(
-                "Expected int, got boolean during addition operation.",
-            )),
-            e => e,
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match numeric_operands("+", lnode, rnode, inputs) {
+            Ok((l, r)) => checked("+", l.checked_add(r)),
+            Err(e) => e,
         }
     }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("+", lvalue, rvalue, NodeType::Numeric, NodeType::Numeric)
+    }
 }
 
-pub struct EqualsOperation {}
+pub struct SubtractionOperation {}
 
-impl BinaryOperation for EqualsOperation {
-    fn eval(
-        &self,
-        lnode: &mut Box<EvalNode>,
-        rnode: &mut Box<EvalNode>,
-        inputs: &mut DecisionDataset,
-    ) -> NodeResult {
-        match lnode.eval(inputs) {
-            NodeResult::Numeric(l) => match rnode.eval(inputs) {
-                NodeResult::Numeric(r) => NodeResult::Boolean(l == r),
-                NodeResult::Boolean(_) => NodeResult::Err(String::from(
-                    "Cannot compare equality between int with boolean.",
-                )),
-                e => e,
-            },
-            NodeResult::Boolean(l) => match rnode.eval(inputs) {
-                NodeResult::Numeric(_) => NodeResult::Err(String::from(
-                    "Cannot compare equality between boolean with int.",
-                )),
-                NodeResult::Boolean(r) => NodeResult::Boolean(l == r),
-                e => e,
-            },
-            e => e,
+impl BinaryOperation for SubtractionOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match numeric_operands("-", lnode, rnode, inputs) {
+            Ok((l, r)) => checked("-", l.checked_sub(r)),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("-", lvalue, rvalue, NodeType::Numeric, NodeType::Numeric)
+    }
+}
+
+pub struct MultiplicationOperation {}
+
+impl BinaryOperation for MultiplicationOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match numeric_operands("*", lnode, rnode, inputs) {
+            Ok((l, r)) => checked("*", l.checked_mul(r)),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("*", lvalue, rvalue, NodeType::Numeric, NodeType::Numeric)
+    }
+}
+
+pub struct DivisionOperation {}
+
+impl BinaryOperation for DivisionOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match numeric_operands("/", lnode, rnode, inputs) {
+            Ok((_, r)) if r.is_zero() => NodeResult::Err(String::from("Division by zero.")),
+            Ok((l, r)) => checked("/", l.checked_div(r)),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("/", lvalue, rvalue, NodeType::Numeric, NodeType::Numeric)
+    }
+}
+
+pub struct RemainderOperation {}
+
+impl BinaryOperation for RemainderOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match numeric_operands("%", lnode, rnode, inputs) {
+            Ok((_, r)) if r.is_zero() => NodeResult::Err(String::from("Division by zero.")),
+            Ok((l, r)) => checked("%", l.checked_rem(r)),
+            Err(e) => e,
         }
     }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("%", lvalue, rvalue, NodeType::Numeric, NodeType::Numeric)
+    }
 }
 
 pub struct PowerOperation {}
 
 impl BinaryOperation for PowerOperation {
-    fn eval(
-        &self,
-        lnode: &mut Box<EvalNode>,
-        rnode: &mut Box<EvalNode>,
-        inputs: &mut DecisionDataset,
-    ) -> NodeResult {
-        match lnode.eval(inputs) {
-            NodeResult::Numeric(l) => match rnode.eval(inputs) {
-                NodeResult::Numeric(r) => NodeResult::Numeric(l.pow(r as u32)),
-                NodeResult::Boolean(_) => NodeResult::Err(String::from(
-                    "Expected int, got boolean during addition operation.",
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match numeric_operands("pow", lnode, rnode, inputs) {
+            Ok((l, r)) => match r.to_u64() {
+                Some(exponent) if r.fract().is_zero() => checked("pow", power(l, exponent)),
+                _ => NodeResult::Err(String::from(
+                    "Operation pow expects a non-negative integer exponent.",
                 )),
-                e => e,
             },
-            NodeResult::Boolean(_) => NodeResult::Err(String::from(
-                "Expected int, got boolean during addition operation.",
-            )),
-            e => e,
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("pow", lvalue, rvalue, NodeType::Numeric, NodeType::Numeric)
+    }
+}
+
+pub struct MinOperation {}
+
+impl BinaryOperation for MinOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match numeric_operands("min", lnode, rnode, inputs) {
+            Ok((l, r)) => NodeResult::Numeric(l.min(r)),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("min", lvalue, rvalue, NodeType::Numeric, NodeType::Numeric)
+    }
+}
+
+pub struct MaxOperation {}
+
+impl BinaryOperation for MaxOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match numeric_operands("max", lnode, rnode, inputs) {
+            Ok((l, r)) => NodeResult::Numeric(l.max(r)),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("max", lvalue, rvalue, NodeType::Numeric, NodeType::Numeric)
+    }
+}
+
+pub struct EqualsOperation {}
+
+impl BinaryOperation for EqualsOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match equals("==", lnode, rnode, inputs) {
+            Ok(b) => NodeResult::Boolean(b),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        equality_type("==", lvalue, rvalue)
+    }
+}
+
+pub struct NotEqualsOperation {}
+
+impl BinaryOperation for NotEqualsOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match equals("!=", lnode, rnode, inputs) {
+            Ok(b) => NodeResult::Boolean(!b),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        equality_type("!=", lvalue, rvalue)
+    }
+}
+
+pub struct GreaterThanOperation {}
+
+impl BinaryOperation for GreaterThanOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match compare(">", lnode, rnode, inputs) {
+            Ok(o) => NodeResult::Boolean(o == Ordering::Greater),
+            Err(e) => e,
         }
     }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        comparison_type(">", lvalue, rvalue)
+    }
 }
 
 pub struct GreaterThanOrEqualsOperation {}
 
 impl BinaryOperation for GreaterThanOrEqualsOperation {
-    fn eval(
-        &self,
-        lnode: &mut Box<EvalNode>,
-        rnode: &mut Box<EvalNode>,
-        inputs: &mut DecisionDataset,
-    ) -> NodeResult {
-        match lnode.eval(inputs) {
-            NodeResult::Numeric(l) => match rnode.eval(inputs) {
-                NodeResult::Numeric(r) => NodeResult::Boolean(l >= r),
-                NodeResult::Boolean(_) => NodeResult::Err(String::from(
-                    "Expected int, got boolean during addition operation.",
-                )),
-                e => e,
-            },
-            NodeResult::Boolean(_) => NodeResult::Err(String::from(
-                "Expected int, got boolean during addition operation.",
-            )),
-            e => e,
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match compare(">=", lnode, rnode, inputs) {
+            Ok(o) => NodeResult::Boolean(o != Ordering::Less),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        comparison_type(">=", lvalue, rvalue)
+    }
+}
+
+pub struct LessThanOperation {}
+
+impl BinaryOperation for LessThanOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match compare("<", lnode, rnode, inputs) {
+            Ok(o) => NodeResult::Boolean(o == Ordering::Less),
+            Err(e) => e,
         }
     }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        comparison_type("<", lvalue, rvalue)
+    }
 }
 
 pub struct LessThanOrEqualsOperation {}
 
 impl BinaryOperation for LessThanOrEqualsOperation {
-    fn eval(
-        &self,
-        lnode: &mut Box<EvalNode>,
-        rnode: &mut Box<EvalNode>,
-        inputs: &mut DecisionDataset,
-    ) -> NodeResult {
-        match lnode.eval(inputs) {
-            NodeResult::Numeric(l) => match rnode.eval(inputs) {
-                NodeResult::Numeric(r) => NodeResult::Boolean(l <= r),
-                NodeResult::Boolean(_) => NodeResult::Err(String::from(
-                    "Expected int, got boolean during addition operation.",
-                )),
-                e => e,
-            },
-            NodeResult::Boolean(_) => NodeResult::Err(String::from(
-                "Expected int, got boolean during addition operation.",
-            )),
-            e => e,
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match compare("<=", lnode, rnode, inputs) {
+            Ok(o) => NodeResult::Boolean(o != Ordering::Greater),
+            Err(e) => e,
         }
     }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        comparison_type("<=", lvalue, rvalue)
+    }
 }
 
 pub struct AndOperation {}
 
 impl BinaryOperation for AndOperation {
-    fn eval(
-        &self,
-        lnode: &mut Box<EvalNode>,
-        rnode: &mut Box<EvalNode>,
-        inputs: &mut DecisionDataset,
-    ) -> NodeResult {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
         match lnode.eval(inputs) {
-            NodeResult::Boolean(true) => match rnode.eval(inputs) {
-                NodeResult::Boolean(b) => NodeResult::Boolean(b),
-                NodeResult::Numeric(_) => {
-                    NodeResult::Err(String::from("Expected bool, got int during AND operation."))
-                }
-                e => e,
-            },
             NodeResult::Boolean(false) => NodeResult::Boolean(false),
-            NodeResult::Numeric(_) => {
-                NodeResult::Err(String::from("Expected bool, got int during AND operation."))
+            e @ NodeResult::Err(_) => e,
+            l @ NodeResult::Boolean(true) | l @ NodeResult::Null => match (l, rnode.eval(inputs)) {
+                (_, NodeResult::Boolean(false)) => NodeResult::Boolean(false),
+                (_, e @ NodeResult::Err(_)) => e,
+                (NodeResult::Boolean(true), NodeResult::Boolean(true)) => NodeResult::Boolean(true),
+                (_, NodeResult::Boolean(true)) | (_, NodeResult::Null) => NodeResult::Null,
+                (l, r) => operands_type_error("&&", &NodeType::Boolean, &l, &r),
+            },
+            l => operands_type_error("&&", &NodeType::Boolean, &l, &rnode.eval(inputs)),
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("&&", lvalue, rvalue, NodeType::Boolean, NodeType::Boolean)
+    }
+}
+
+pub struct OrOperation {}
+
+impl BinaryOperation for OrOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match lnode.eval(inputs) {
+            NodeResult::Boolean(true) => NodeResult::Boolean(true),
+            e @ NodeResult::Err(_) => e,
+            l @ NodeResult::Boolean(false) | l @ NodeResult::Null => {
+                match (l, rnode.eval(inputs)) {
+                    (_, NodeResult::Boolean(true)) => NodeResult::Boolean(true),
+                    (_, e @ NodeResult::Err(_)) => e,
+                    (NodeResult::Boolean(false), NodeResult::Boolean(false)) => {
+                        NodeResult::Boolean(false)
+                    }
+                    (_, NodeResult::Boolean(false)) | (_, NodeResult::Null) => NodeResult::Null,
+                    (l, r) => operands_type_error("||", &NodeType::Boolean, &l, &r),
+                }
             }
-            e => e,
+            l => operands_type_error("||", &NodeType::Boolean, &l, &rnode.eval(inputs)),
         }
     }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("||", lvalue, rvalue, NodeType::Boolean, NodeType::Boolean)
+    }
 }
 
 pub struct ArrayContainsOperation {}
 
 impl BinaryOperation for ArrayContainsOperation {
-    fn eval(
-        &self,
-        lnode: &mut Box<EvalNode>,
-        rnode: &mut Box<EvalNode>,
-        inputs: &mut DecisionDataset,
-    ) -> NodeResult {
-        match lnode.eval(inputs) {
-            NodeResult::Array(v) => NodeResult::Boolean(v.contains(&rnode.eval(inputs))),
-            _ => NodeResult::Err(String::from(
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match eval_operands(lnode, rnode, inputs) {
+            Ok((NodeResult::Array(v), r)) => NodeResult::Boolean(v.contains(&r)),
+            Ok(_) => NodeResult::Err(String::from(
                 "lvalue of array_contains operation is not an array.",
             )),
+            Err(e) => e,
         }
     }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        match lvalue {
+            NodeType::Array(element_type) if element_type.is_compatible(rvalue) => {
+                Ok(NodeType::Boolean)
+            }
+            NodeType::Any => Ok(NodeType::Boolean),
+            _ => Err(format!(
+                "Operation array_contains expects (Array<{}>, {}), got ({}, {}).",
+                rvalue, rvalue, lvalue, rvalue
+            )),
+        }
+    }
+}
+
+fn compile_regex(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|_| format!("Invalid regular expression {}", pattern))
 }
 
-pub struct RegexContainsOperation {}
+/// Whether the left operand matches the pattern on the right. A constant
+/// pattern is compiled once, when the strategy loads.
+pub struct RegexContainsOperation {
+    pub regex: Option<Regex>,
+}
 
 impl BinaryOperation for RegexContainsOperation {
-    fn eval(
-        &self,
-        lnode: &mut Box<EvalNode>,
-        rnode: &mut Box<EvalNode>,
-        inputs: &mut DecisionDataset,
-    ) -> NodeResult {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match eval_operands(lnode, rnode, inputs) {
+            Ok((NodeResult::Text(t), NodeResult::Text(pattern))) => match self.regex {
+                Some(ref regex) => NodeResult::Boolean(regex.is_match(&t)),
+                None => match compile_regex(&pattern) {
+                    Ok(regex) => NodeResult::Boolean(regex.is_match(&t)),
+                    Err(msg) => NodeResult::Err(msg),
+                },
+            },
+            Ok((l, r)) => operands_type_error("regex_contains", &NodeType::Text, &l, &r),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands(
+            "regex_contains",
+            lvalue,
+            rvalue,
+            NodeType::Text,
+            NodeType::Boolean,
+        )
+    }
+
+    fn bind_constant_rvalue(&mut self, rvalue: &NodeResult) -> Result<(), String> {
+        if let NodeResult::Text(pattern) = rvalue {
+            self.regex = Some(compile_regex(pattern)?);
+        }
+        Ok(())
+    }
+}
+
+/// Whole years from the first date to the second, as used for ages.
+pub fn years_between(from: NaiveDate, to: NaiveDate) -> i32 {
+    let mut years = to.year() - from.year();
+    if (to.month(), to.day()) < (from.month(), from.day()) {
+        years -= 1;
+    }
+    years
+}
+
+pub struct YearsBetweenOperation {}
+
+impl BinaryOperation for YearsBetweenOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match date_operands("years_between", lnode, rnode, inputs) {
+            Ok((from, to)) => NodeResult::Numeric(Decimal::from(years_between(from, to))),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands(
+            "years_between",
+            lvalue,
+            rvalue,
+            NodeType::Date,
+            NodeType::Numeric,
+        )
+    }
+}
+
+pub struct DaysBetweenOperation {}
+
+impl BinaryOperation for DaysBetweenOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match date_operands("days_between", lnode, rnode, inputs) {
+            Ok((from, to)) => {
+                NodeResult::Numeric(Decimal::from(to.signed_duration_since(from).num_days()))
+            }
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands(
+            "days_between",
+            lvalue,
+            rvalue,
+            NodeType::Date,
+            NodeType::Numeric,
+        )
+    }
+}
+
+pub struct AddDaysOperation {}
+
+impl BinaryOperation for AddDaysOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match eval_operands(lnode, rnode, inputs) {
+            Ok((NodeResult::Date(date), NodeResult::Numeric(days))) => {
+                if !days.fract().is_zero() {
+                    return NodeResult::Err(String::from(
+                        "Operation add_days expects a whole number of days.",
+                    ));
+                }
+                // Building a Duration panics far beyond the range of dates,
+                // so offsets that cannot fit an i32 overflow without one.
+                match days
+                    .to_i32()
+                    .and_then(|days| date.checked_add_signed(Duration::days(i64::from(days))))
+                {
+                    Some(date) => NodeResult::Date(date),
+                    None => NodeResult::Err(String::from("Operation add_days overflowed.")),
+                }
+            }
+            Ok((l, r)) => match (type_of(&l), type_of(&r)) {
+                (Err(msg), _) | (_, Err(msg)) => NodeResult::Err(msg),
+                (Ok(ltype), Ok(rtype)) => NodeResult::Err(format!(
+                    "Operation add_days expects (Date, Numeric), got ({}, {}).",
+                    ltype, rtype
+                )),
+            },
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        add_days_type(lvalue, rvalue)
+    }
+}
+
+fn add_days_type(lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+    if lvalue.is_compatible(&NodeType::Date) && rvalue.is_compatible(&NodeType::Numeric) {
+        Ok(NodeType::Date)
+    } else {
+        Err(format!(
+            "Operation add_days expects (Date, Numeric), got ({}, {}).",
+            lvalue, rvalue
+        ))
+    }
+}
+
+/// The left operand, or the right operand if the left one is null.
+pub struct CoalesceOperation {}
+
+impl BinaryOperation for CoalesceOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
         match lnode.eval(inputs) {
-            NodeResult::Text(t) => match rnode.eval(inputs) {
-                NodeResult::Text(pattern) => {
-                    let p = Regex::new(&pattern);
-                    match p {
-                        Ok(p) => NodeResult::Boolean(p.is_match(&t)),
-                        _ => NodeResult::Err(format!("Invalid regular expression {}", pattern)),
+            NodeResult::Null => rnode.eval(inputs),
+            l => l,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        match lvalue {
+            NodeType::Any => Ok(rvalue.clone()),
+            _ => check_operands("coalesce", lvalue, rvalue, lvalue.clone(), lvalue.clone()),
+        }
+    }
+}
+
+pub struct IsNullOperation {}
+
+impl UnaryOperation for IsNullOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match node.eval(inputs) {
+            NodeResult::Null => NodeResult::Boolean(true),
+            e @ NodeResult::Err(_) => e,
+            _ => NodeResult::Boolean(false),
+        }
+    }
+
+    fn result_type(&self, _value: &NodeType) -> Result<NodeType, String> {
+        Ok(NodeType::Boolean)
+    }
+}
+
+pub struct NotOperation {}
+
+impl UnaryOperation for NotOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match node.eval(inputs) {
+            NodeResult::Boolean(b) => NodeResult::Boolean(!b),
+            NodeResult::Null => NodeResult::Null,
+            v => operand_type_error("!", &NodeType::Boolean, &v),
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand("!", value, NodeType::Boolean, NodeType::Boolean)
+    }
+}
+
+pub struct NegateOperation {}
+
+impl UnaryOperation for NegateOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match node.eval(inputs) {
+            NodeResult::Numeric(n) => NodeResult::Numeric(-n),
+            NodeResult::Null => NodeResult::Null,
+            v => operand_type_error("neg", &NodeType::Numeric, &v),
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand("neg", value, NodeType::Numeric, NodeType::Numeric)
+    }
+}
+
+pub struct AbsOperation {}
+
+impl UnaryOperation for AbsOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match node.eval(inputs) {
+            NodeResult::Numeric(n) => NodeResult::Numeric(n.abs()),
+            NodeResult::Null => NodeResult::Null,
+            v => operand_type_error("abs", &NodeType::Numeric, &v),
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand("abs", value, NodeType::Numeric, NodeType::Numeric)
+    }
+}
+
+/// Calendar date, in UTC, of a datetime.
+pub struct DateOperation {}
+
+impl UnaryOperation for DateOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match node.eval(inputs) {
+            NodeResult::DateTime(d) => NodeResult::Date(d.naive_utc().date()),
+            NodeResult::Null => NodeResult::Null,
+            v => operand_type_error("date", &NodeType::DateTime, &v),
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand("date", value, NodeType::DateTime, NodeType::Date)
+    }
+}
+
+pub struct LowerOperation {}
+
+impl UnaryOperation for LowerOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match text_operand("lower", node, inputs) {
+            Ok(t) => NodeResult::Text(t.to_lowercase()),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand("lower", value, NodeType::Text, NodeType::Text)
+    }
+}
+
+pub struct UpperOperation {}
+
+impl UnaryOperation for UpperOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match text_operand("upper", node, inputs) {
+            Ok(t) => NodeResult::Text(t.to_uppercase()),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand("upper", value, NodeType::Text, NodeType::Text)
+    }
+}
+
+/// Strips leading and trailing whitespace.
+pub struct TrimOperation {}
+
+impl UnaryOperation for TrimOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match text_operand("trim", node, inputs) {
+            Ok(t) => NodeResult::Text(t.trim().to_string()),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand("trim", value, NodeType::Text, NodeType::Text)
+    }
+}
+
+/// Number of characters (Unicode scalar values) in a string.
+pub struct LengthOperation {}
+
+impl UnaryOperation for LengthOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match text_operand("length", node, inputs) {
+            Ok(t) => NodeResult::Numeric(Decimal::from(t.chars().count() as u64)),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand("length", value, NodeType::Text, NodeType::Numeric)
+    }
+}
+
+pub struct StartsWithOperation {}
+
+impl BinaryOperation for StartsWithOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match text_operands("starts_with", lnode, rnode, inputs) {
+            Ok((l, r)) => NodeResult::Boolean(l.starts_with(&r[..])),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands(
+            "starts_with",
+            lvalue,
+            rvalue,
+            NodeType::Text,
+            NodeType::Boolean,
+        )
+    }
+}
+
+pub struct EndsWithOperation {}
+
+impl BinaryOperation for EndsWithOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match text_operands("ends_with", lnode, rnode, inputs) {
+            Ok((l, r)) => NodeResult::Boolean(l.ends_with(&r[..])),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands(
+            "ends_with",
+            lvalue,
+            rvalue,
+            NodeType::Text,
+            NodeType::Boolean,
+        )
+    }
+}
+
+pub struct ConcatOperation {}
+
+impl BinaryOperation for ConcatOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match text_operands("concat", lnode, rnode, inputs) {
+            Ok((l, r)) => NodeResult::Text(l + &r),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("concat", lvalue, rvalue, NodeType::Text, NodeType::Text)
+    }
+}
+
+/// Unicode normalization of the left operand to the form named by the right
+/// operand: `"NFC"`, `"NFD"`, `"NFKC"` or `"NFKD"`.
+pub struct NormalizeOperation {}
+
+impl BinaryOperation for NormalizeOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match text_operands("normalize", lnode, rnode, inputs) {
+            Ok((t, form)) => match &form[..] {
+                "NFC" => NodeResult::Text(t.nfc().collect()),
+                "NFD" => NodeResult::Text(t.nfd().collect()),
+                "NFKC" => NodeResult::Text(t.nfkc().collect()),
+                "NFKD" => NodeResult::Text(t.nfkd().collect()),
+                _ => NodeResult::Err(format!("Unknown normalization form {}.", form)),
+            },
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        check_operands("normalize", lvalue, rvalue, NodeType::Text, NodeType::Text)
+    }
+}
+
+/// Characters of a string from a zero-based start position, taking at most
+/// the given length. Positions past the end of the string are clamped.
+pub struct SubstringOperation {}
+
+impl SubstringOperation {
+    fn position(n: Decimal) -> Result<usize, NodeResult> {
+        match n.to_u64().filter(|_| n.fract().is_zero()) {
+            Some(n) => Ok(n as usize),
+            None => Err(NodeResult::Err(String::from(
+                "Operation substring expects non-negative integer positions.",
+            ))),
+        }
+    }
+}
+
+impl TernaryOperation for SubstringOperation {
+    fn eval(&self, nodes: &[Box<EvalNode>; 3], inputs: &mut DecisionDataset) -> NodeResult {
+        let mut values = Vec::new();
+        for node in nodes.iter() {
+            match node.eval(inputs) {
+                e @ NodeResult::Err(_) => return e,
+                value => values.push(value),
+            }
+        }
+        if values.contains(&NodeResult::Null) {
+            return NodeResult::Null;
+        }
+        match (&values[0], &values[1], &values[2]) {
+            (NodeResult::Text(text), NodeResult::Numeric(start), NodeResult::Numeric(length)) => {
+                match (Self::position(*start), Self::position(*length)) {
+                    (Ok(start), Ok(length)) => {
+                        NodeResult::Text(text.chars().skip(start).take(length).collect())
                     }
+                    (Err(e), _) | (_, Err(e)) => e,
                 }
-                _ => NodeResult::Err(String::from(
-                    "rvalue of regex_contains operation is not an string.",
-                )),
+            }
+            _ => match (
+                type_of(&values[0]),
+                type_of(&values[1]),
+                type_of(&values[2]),
+            ) {
+                (Ok(text), Ok(start), Ok(length)) => NodeResult::Err(
+                    self.result_type(&[text, start, length])
+                        .err()
+                        .unwrap_or_default(),
+                ),
+                (Err(msg), _, _) | (_, Err(msg), _) | (_, _, Err(msg)) => NodeResult::Err(msg),
             },
-            _ => NodeResult::Err(String::from(
-                "lvalue of array_contains operation is not an string.",
+        }
+    }
+
+    fn result_type(&self, values: &[NodeType; 3]) -> Result<NodeType, String> {
+        if values[0].is_compatible(&NodeType::Text)
+            && values[1].is_compatible(&NodeType::Numeric)
+            && values[2].is_compatible(&NodeType::Numeric)
+        {
+            Ok(NodeType::Text)
+        } else {
+            Err(format!(
+                "Operation substring expects (Text, Numeric, Numeric), got ({}, {}, {}).",
+                values[0], values[1], values[2]
+            ))
+        }
+    }
+}
+
+fn array_operand(
+    op: &str,
+    node: &EvalNode,
+    inputs: &mut DecisionDataset,
+) -> Result<Vec<NodeResult>, NodeResult> {
+    match node.eval(inputs) {
+        NodeResult::Array(values) => Ok(values),
+        NodeResult::Null => Err(NodeResult::Null),
+        v => Err(operand_type_error(
+            op,
+            &NodeType::Array(Box::new(NodeType::Any)),
+            &v,
+        )),
+    }
+}
+
+/// Evaluates `node` with `element` bound as the current array element.
+fn eval_element(node: &EvalNode, element: NodeResult, inputs: &mut DecisionDataset) -> NodeResult {
+    inputs.push_element(element);
+    let result = node.eval(inputs);
+    inputs.pop_element();
+    result
+}
+
+/// Evaluates the predicate of `any`, `all`, `count` or `filter` for an
+/// element. A null predicate does not hold.
+fn holds(
+    op: &str,
+    predicate: &EvalNode,
+    element: NodeResult,
+    inputs: &mut DecisionDataset,
+) -> Result<bool, NodeResult> {
+    match eval_element(predicate, element, inputs) {
+        NodeResult::Boolean(b) => Ok(b),
+        NodeResult::Null => Ok(false),
+        e @ NodeResult::Err(_) => Err(e),
+        v => Err(NodeResult::Err(format!(
+            "Operation {} expects a Boolean predicate, got {}.",
+            op,
+            type_of(&v).unwrap_or(NodeType::Any)
+        ))),
+    }
+}
+
+/// Checks that `lvalue` is an array and `rvalue`, evaluated per element, is
+/// `expected`.
+fn array_operation_type(
+    op: &str,
+    lvalue: &NodeType,
+    rvalue: &NodeType,
+    expected: NodeType,
+) -> Result<(), String> {
+    let is_array = match lvalue {
+        NodeType::Array(_) | NodeType::Any => true,
+        _ => false,
+    };
+    if is_array && rvalue.is_compatible(&expected) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Operation {} expects (Array, {}), got ({}, {}).",
+            op, expected, lvalue, rvalue
+        ))
+    }
+}
+
+/// Whether the predicate on the right holds for any element of the array on
+/// the left.
+pub struct AnyOperation {}
+
+impl BinaryOperation for AnyOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        let values = match array_operand("any", lnode, inputs) {
+            Ok(values) => values,
+            Err(e) => return e,
+        };
+        for value in values {
+            match holds("any", rnode, value, inputs) {
+                Ok(true) => return NodeResult::Boolean(true),
+                Ok(false) => {}
+                Err(e) => return e,
+            }
+        }
+        NodeResult::Boolean(false)
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        array_operation_type("any", lvalue, rvalue, NodeType::Boolean)?;
+        Ok(NodeType::Boolean)
+    }
+
+    fn binds_element(&self) -> bool {
+        true
+    }
+}
+
+/// Whether the predicate on the right holds for every element of the array
+/// on the left.
+pub struct AllOperation {}
+
+impl BinaryOperation for AllOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        let values = match array_operand("all", lnode, inputs) {
+            Ok(values) => values,
+            Err(e) => return e,
+        };
+        for value in values {
+            match holds("all", rnode, value, inputs) {
+                Ok(true) => {}
+                Ok(false) => return NodeResult::Boolean(false),
+                Err(e) => return e,
+            }
+        }
+        NodeResult::Boolean(true)
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        array_operation_type("all", lvalue, rvalue, NodeType::Boolean)?;
+        Ok(NodeType::Boolean)
+    }
+
+    fn binds_element(&self) -> bool {
+        true
+    }
+}
+
+/// Number of elements of the array on the left for which the predicate on
+/// the right holds.
+pub struct CountOperation {}
+
+impl BinaryOperation for CountOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        let values = match array_operand("count", lnode, inputs) {
+            Ok(values) => values,
+            Err(e) => return e,
+        };
+        let mut count: u64 = 0;
+        for value in values {
+            match holds("count", rnode, value, inputs) {
+                Ok(true) => count += 1,
+                Ok(false) => {}
+                Err(e) => return e,
+            }
+        }
+        NodeResult::Numeric(Decimal::from(count))
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        array_operation_type("count", lvalue, rvalue, NodeType::Boolean)?;
+        Ok(NodeType::Numeric)
+    }
+
+    fn binds_element(&self) -> bool {
+        true
+    }
+}
+
+/// Elements of the array on the left for which the predicate on the right
+/// holds.
+pub struct FilterOperation {}
+
+impl BinaryOperation for FilterOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        let values = match array_operand("filter", lnode, inputs) {
+            Ok(values) => values,
+            Err(e) => return e,
+        };
+        let mut filtered = Vec::new();
+        for value in values {
+            match holds("filter", rnode, value.clone(), inputs) {
+                Ok(true) => filtered.push(value),
+                Ok(false) => {}
+                Err(e) => return e,
+            }
+        }
+        NodeResult::Array(filtered)
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        array_operation_type("filter", lvalue, rvalue, NodeType::Boolean)?;
+        match lvalue {
+            NodeType::Any => Ok(NodeType::Array(Box::new(NodeType::Any))),
+            _ => Ok(lvalue.clone()),
+        }
+    }
+
+    fn binds_element(&self) -> bool {
+        true
+    }
+}
+
+/// The expression on the right evaluated for each element of the array on
+/// the left.
+pub struct MapOperation {}
+
+impl BinaryOperation for MapOperation {
+    fn eval(&self, lnode: &EvalNode, rnode: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        let values = match array_operand("map", lnode, inputs) {
+            Ok(values) => values,
+            Err(e) => return e,
+        };
+        let mut mapped = Vec::new();
+        for value in values {
+            match eval_element(rnode, value, inputs) {
+                e @ NodeResult::Err(_) => return e,
+                value => mapped.push(value),
+            }
+        }
+        NodeResult::Array(mapped)
+    }
+
+    fn result_type(&self, lvalue: &NodeType, rvalue: &NodeType) -> Result<NodeType, String> {
+        array_operation_type("map", lvalue, rvalue, NodeType::Any)?;
+        Ok(NodeType::Array(Box::new(rvalue.clone())))
+    }
+
+    fn binds_element(&self) -> bool {
+        true
+    }
+}
+
+/// Numeric elements of an array, skipping nulls.
+fn numeric_elements(op: &str, values: Vec<NodeResult>) -> Result<Vec<Decimal>, NodeResult> {
+    let mut numbers = Vec::new();
+    for value in values {
+        match value {
+            NodeResult::Numeric(n) => numbers.push(n),
+            NodeResult::Null => {}
+            v => {
+                return Err(operand_type_error(
+                    op,
+                    &NodeType::Array(Box::new(NodeType::Numeric)),
+                    &NodeResult::Array(vec![v]),
+                ))
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+/// Total of a numeric array, ignoring null elements.
+pub struct SumOperation {}
+
+impl UnaryOperation for SumOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        let numbers = match array_operand("sum", node, inputs)
+            .and_then(|values| numeric_elements("sum", values))
+        {
+            Ok(numbers) => numbers,
+            Err(e) => return e,
+        };
+        let mut sum = Decimal::from(0);
+        for n in numbers {
+            sum = match sum.checked_add(n) {
+                Some(sum) => sum,
+                None => return checked("sum", None),
+            };
+        }
+        NodeResult::Numeric(sum)
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand(
+            "sum",
+            value,
+            NodeType::Array(Box::new(NodeType::Numeric)),
+            NodeType::Numeric,
+        )
+    }
+}
+
+/// Smallest element of a numeric array, ignoring null elements. Null if the
+/// array has no numbers.
+pub struct ArrayMinOperation {}
+
+impl UnaryOperation for ArrayMinOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match array_operand("min", node, inputs).and_then(|values| numeric_elements("min", values))
+        {
+            Ok(numbers) => numbers
+                .into_iter()
+                .min()
+                .map(NodeResult::Numeric)
+                .unwrap_or(NodeResult::Null),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand(
+            "min",
+            value,
+            NodeType::Array(Box::new(NodeType::Numeric)),
+            NodeType::Numeric,
+        )
+    }
+}
+
+/// Largest element of a numeric array, ignoring null elements. Null if the
+/// array has no numbers.
+pub struct ArrayMaxOperation {}
+
+impl UnaryOperation for ArrayMaxOperation {
+    fn eval(&self, node: &EvalNode, inputs: &mut DecisionDataset) -> NodeResult {
+        match array_operand("max", node, inputs).and_then(|values| numeric_elements("max", values))
+        {
+            Ok(numbers) => numbers
+                .into_iter()
+                .max()
+                .map(NodeResult::Numeric)
+                .unwrap_or(NodeResult::Null),
+            Err(e) => e,
+        }
+    }
+
+    fn result_type(&self, value: &NodeType) -> Result<NodeType, String> {
+        check_operand(
+            "max",
+            value,
+            NodeType::Array(Box::new(NodeType::Numeric)),
+            NodeType::Numeric,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use decisionengine::deserialize_strategy;
+    use decisionengine::errors::DecisionEngineError;
+    use decisionengine::nodes::NodeResult;
+    use decisionengine::testing::{dataset, eval, eval_with, rule_strategy};
+    use decisionengine::validation::validate_strategy;
+    use rust_decimal::Decimal;
+    use serde_json::Value;
+    use std::str::FromStr;
+
+    fn date(year: i32, month: u32, day: u32) -> NodeResult {
+        NodeResult::Date(NaiveDate::from_ymd(year, month, day))
+    }
+
+    fn number(value: &str) -> NodeResult {
+        NodeResult::Numeric(Decimal::from_str(value).unwrap())
+    }
+
+    fn expression(source: &str) -> NodeResult {
+        eval(Value::from(source))
+    }
+
+    fn error(message: &str) -> NodeResult {
+        NodeResult::Err(message.to_string())
+    }
+
+    #[test]
+    fn arithmetic_follows_precedence() {
+        assert_eq!(expression("1 + 2 * 3"), number("7"));
+        assert_eq!(expression("(1 + 2) * 3"), number("9"));
+        assert_eq!(expression("10 - 4 - 3"), number("3"));
+        assert_eq!(expression("7 / 2"), number("3.5"));
+        assert_eq!(expression("7 % 4"), number("3"));
+        assert_eq!(expression("pow(2, 10)"), number("1024"));
+        assert_eq!(expression("min(3, 4)"), number("3"));
+        assert_eq!(expression("max(3, 4)"), number("4"));
+        assert_eq!(expression("neg(2)"), number("-2"));
+        assert_eq!(expression("abs(1 - 5)"), number("4"));
+    }
+
+    #[test]
+    fn arithmetic_errors_are_reported() {
+        assert_eq!(
+            expression("application_data_v1.age / 0"),
+            error("Division by zero.")
+        );
+        assert_eq!(
+            expression("application_data_v1.age % 0"),
+            error("Division by zero.")
+        );
+        assert_eq!(
+            expression("pow(application_data_v1.age, 0.5)"),
+            error("Operation pow expects a non-negative integer exponent.")
+        );
+        match expression("pow(application_data_v1.age, 100)") {
+            NodeResult::Err(_) => {}
+            other => panic!("expected an overflow, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(expression("2 >= 2"), NodeResult::Boolean(true));
+        assert_eq!(expression("2 > 2"), NodeResult::Boolean(false));
+        assert_eq!(expression("1 <= 2"), NodeResult::Boolean(true));
+        assert_eq!(expression("3 < 2"), NodeResult::Boolean(false));
+        assert_eq!(expression("1 != 2"), NodeResult::Boolean(true));
+        assert_eq!(expression("\"a\" == \"a\""), NodeResult::Boolean(true));
+        assert_eq!(
+            expression("date(\"2018-01-01\") < date(\"2018-01-02\")"),
+            NodeResult::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(expression("true && false"), NodeResult::Boolean(false));
+        assert_eq!(expression("false || true"), NodeResult::Boolean(true));
+        assert_eq!(expression("!true"), NodeResult::Boolean(false));
+        assert_eq!(
+            expression("false && application_data_v1.age / 0 > 1"),
+            NodeResult::Boolean(false)
+        );
+        assert_eq!(
+            expression("true || application_data_v1.age / 0 > 1"),
+            NodeResult::Boolean(true)
+        );
+        assert_eq!(
+            expression("true && application_data_v1.age / 0 > 1"),
+            error("Division by zero.")
+        );
+    }
+
+    fn text(value: &str) -> NodeResult {
+        NodeResult::Text(value.to_string())
+    }
+
+    #[test]
+    fn string_operators() {
+        assert_eq!(
+            expression("lower(application_data_v1.first_name)"),
+            text("tony")
+        );
+        assert_eq!(
+            expression("upper(application_data_v1.first_name)"),
+            text("TONY")
+        );
+        assert_eq!(expression("trim(\"  Tony \\t\")"), text("Tony"));
+        assert_eq!(expression("length(\"Zoë\")"), number("3"));
+        assert_eq!(
+            expression("concat(application_data_v1.first_name, \" Stark\")"),
+            text("Tony Stark")
+        );
+        assert_eq!(
+            expression("starts_with(application_data_v1.last_name, \"St\")"),
+            NodeResult::Boolean(true)
+        );
+        assert_eq!(
+            expression("ends_with(application_data_v1.last_name, \"St\")"),
+            NodeResult::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn substring_clamps_to_the_string() {
+        assert_eq!(
+            expression("substring(application_data_v1.last_name, 1, 3)"),
+            text("tar")
+        );
+        assert_eq!(
+            expression("substring(application_data_v1.last_name, 3, 10)"),
+            text("rk")
+        );
+        assert_eq!(
+            expression("substring(application_data_v1.last_name, 10, 1)"),
+            text("")
+        );
+        assert_eq!(
+            expression("substring(application_data_v1.last_name, 0 - 1, 1)"),
+            error("Operation substring expects non-negative integer positions.")
+        );
+    }
+
+    #[test]
+    fn normalize_applies_unicode_forms() {
+        let mut input = dataset(json!({"first_name": "Zoe\u{308}"}));
+        let normalize = |form: &str, input: &mut _| {
+            eval_with(
+                Value::from(format!(
+                    "normalize(application_data_v1.first_name, \"{}\")",
+                    form
+                )),
+                input,
+            )
+        };
+        assert_eq!(normalize("NFC", &mut input), text("Zo\u{eb}"));
+        assert_eq!(normalize("NFD", &mut input), text("Zoe\u{308}"));
+        assert_eq!(
+            normalize("NFX", &mut input),
+            error("Unknown normalization form NFX.")
+        );
+    }
+
+    #[test]
+    fn constant_patterns_are_compiled_when_bound() {
+        let mut operation = RegexContainsOperation { regex: None };
+        operation.bind_constant_rvalue(&text("^T")).unwrap();
+        assert!(operation.regex.unwrap().is_match("Tony"));
+
+        let mut operation = RegexContainsOperation { regex: None };
+        assert_eq!(
+            operation.bind_constant_rvalue(&text("(")),
+            Err(String::from("Invalid regular expression ("))
+        );
+    }
+
+    #[test]
+    fn invalid_constant_patterns_fail_to_load() {
+        let strategy = rule_strategy(Value::from(
+            "regex_contains(application_data_v1.first_name, \"(\")",
+        ));
+        match deserialize_strategy(&strategy) {
+            Err(DecisionEngineError::MalformedStrategy(msg)) => {
+                assert_eq!(msg, "Invalid regular expression (")
+            }
+            _ => panic!("expected the pattern to be rejected"),
+        }
+        assert_eq!(validate_strategy(&strategy).len(), 1);
+    }
+
+    #[test]
+    fn regex_contains_matches_patterns() {
+        assert_eq!(
+            expression("regex_contains(application_data_v1.first_name, \"^T.n\")"),
+            NodeResult::Boolean(true)
+        );
+        assert_eq!(
+            expression("regex_contains(application_data_v1.first_name, \"^t\")"),
+            NodeResult::Boolean(false)
+        );
+        // Patterns only known at evaluation are compiled then.
+        let mut input = dataset(json!({"last_name": "("}));
+        assert_eq!(
+            eval_with(
+                Value::from("regex_contains(\"Tony\", application_data_v1.last_name)"),
+                &mut input
+            ),
+            error("Invalid regular expression (")
+        );
+    }
+
+    fn numbers(values: &[&str]) -> NodeResult {
+        NodeResult::Array(values.iter().map(|value| number(value)).collect())
+    }
+
+    #[test]
+    fn quantifiers_apply_a_predicate_to_each_element() {
+        assert_eq!(
+            expression("any([1, 2, 3], element > 2)"),
+            NodeResult::Boolean(true)
+        );
+        assert_eq!(
+            expression("all([1, 2, 3], element > 2)"),
+            NodeResult::Boolean(false)
+        );
+        assert_eq!(expression("count([1, 2, 3], element >= 2)"), number("2"));
+        assert_eq!(
+            expression("filter([1, 2, 3], element != 2)"),
+            numbers(&["1", "3"])
+        );
+        assert_eq!(
+            expression("map([1, 2, 3], element * application_data_v1.age)"),
+            numbers(&["30", "60", "90"])
+        );
+        assert_eq!(
+            expression("any([], element > 2)"),
+            NodeResult::Boolean(false)
+        );
+        assert_eq!(
+            expression("all([], element > 2)"),
+            NodeResult::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn aggregates_skip_nulls() {
+        assert_eq!(expression("sum([1, null, 2.5])"), number("3.5"));
+        assert_eq!(expression("min([3, null, 1, 2])"), number("1"));
+        assert_eq!(expression("max([3, null, 1, 2])"), number("3"));
+        assert_eq!(expression("max([null])"), NodeResult::Null);
+        assert_eq!(expression("sum([])"), number("0"));
+        assert_eq!(
+            expression("array_contains([1, 2, 3], application_data_v1.age / 10)"),
+            NodeResult::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn element_fields_read_bureau_records() {
+        assert_eq!(
+            expression("count(experian_v1_1.accounts, element.status == \"default\")"),
+            number("1")
+        );
+        assert_eq!(
+            expression("sum(map(experian_v1_1.accounts, element.balance))"),
+            number("1550")
+        );
+        assert_eq!(
+            expression("any(experian_v1_1.accounts, element.defaulted_on > date(\"2016-01-01\"))"),
+            NodeResult::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn element_is_only_bound_inside_array_operations() {
+        let strategy = rule_strategy(Value::from("element > 1"));
+        assert_eq!(
+            validate_strategy(&strategy)[0].message,
+            "Element can only be used in the rvalue of an array operation."
+        );
+        let strategy = rule_strategy(Value::from("any([[1], [2, 3]], any(element, element > 2))"));
+        assert!(validate_strategy(&strategy).is_empty());
+        assert_eq!(
+            expression("any([[1], [2, 3]], any(element, element > 2))"),
+            NodeResult::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn missing_inputs_are_null() {
+        assert_eq!(expression("application_data_v1.income"), NodeResult::Null);
+        assert_eq!(
+            expression("application_data_v1.income + 1"),
+            NodeResult::Null
+        );
+        assert_eq!(
+            expression("application_data_v1.income > 1000"),
+            NodeResult::Null
+        );
+        assert_eq!(
+            expression("application_data_v1.income == 1000"),
+            NodeResult::Null
+        );
+        assert_eq!(
+            expression("!(application_data_v1.income > 1000)"),
+            NodeResult::Null
+        );
+    }
+
+    #[test]
+    fn logical_operators_use_three_valued_logic() {
+        let unknown = "application_data_v1.income > 1000";
+        assert_eq!(
+            expression(&format!("{} && false", unknown)),
+            NodeResult::Boolean(false)
+        );
+        assert_eq!(
+            expression(&format!("{} && true", unknown)),
+            NodeResult::Null
+        );
+        assert_eq!(
+            expression(&format!("{} || true", unknown)),
+            NodeResult::Boolean(true)
+        );
+        assert_eq!(
+            expression(&format!("{} || false", unknown)),
+            NodeResult::Null
+        );
+    }
+
+    #[test]
+    fn nulls_can_be_tested_and_replaced() {
+        assert_eq!(
+            expression("is_null(application_data_v1.income)"),
+            NodeResult::Boolean(true)
+        );
+        assert_eq!(
+            expression("is_null(application_data_v1.age)"),
+            NodeResult::Boolean(false)
+        );
+        assert_eq!(
+            expression("coalesce(application_data_v1.income, 0)"),
+            number("0")
+        );
+        assert_eq!(
+            expression("coalesce(application_data_v1.age, 0)"),
+            number("30")
+        );
+    }
+
+    #[test]
+    fn years_between_counts_whole_years() {
+        assert_eq!(
+            expression("years_between(date(\"1990-08-17\"), date(\"2018-08-17\"))"),
+            number("28")
+        );
+        assert_eq!(
+            expression("years_between(date(\"1990-08-18\"), date(\"2018-08-17\"))"),
+            number("27")
+        );
+        assert_eq!(
+            expression("years_between(date(\"2000-02-29\"), date(\"2001-02-28\"))"),
+            number("0")
+        );
+    }
+
+    #[test]
+    fn days_between_counts_days() {
+        assert_eq!(
+            expression("days_between(date(\"2018-01-01\"), date(\"2018-03-01\"))"),
+            number("59")
+        );
+        assert_eq!(
+            expression("days_between(date(\"2018-03-01\"), date(\"2018-01-01\"))"),
+            number("-59")
+        );
+    }
+
+    #[test]
+    fn now_is_read_from_the_clock() {
+        assert_eq!(expression("date(now())"), date(2018, 8, 17));
+        assert_eq!(
+            expression("now() == datetime(\"2018-08-17T14:00:00+02:00\")"),
+            NodeResult::Boolean(true)
+        );
+        let mut input = dataset(json!({"date_of_birth": "2000-08-18"}));
+        assert_eq!(
+            eval_with(
+                Value::from("years_between(application_data_v1.date_of_birth, date(now()))"),
+                &mut input
+            ),
+            number("17")
+        );
+    }
+
+    #[test]
+    fn dates_and_datetimes_do_not_mix() {
+        let strategy = rule_strategy(Value::from("application_data_v1.date_of_birth < now()"));
+        assert_eq!(validate_strategy(&strategy).len(), 1);
+        let strategy = rule_strategy(Value::from("date(\"2018-02-30\") < date(now())"));
+        assert_eq!(validate_strategy(&strategy).len(), 1);
+    }
+
+    #[test]
+    fn add_days_moves_a_date() {
+        assert_eq!(
+            eval(Value::from("add_days(date(\"2018-01-30\"), 3)")),
+            date(2018, 2, 2)
+        );
+        assert_eq!(
+            eval(Value::from("add_days(date(\"2018-01-01\"), -1)")),
+            date(2017, 12, 31)
+        );
+    }
+
+    #[test]
+    fn add_days_rejects_fractional_days() {
+        assert_eq!(
+            eval(Value::from(
+                "add_days(application_data_v1.date_of_birth, 1.5)"
             )),
+            NodeResult::Null
+        );
+        let mut input = dataset(json!({"date_of_birth": "1990-01-01"}));
+        match eval_with(
+            Value::from("add_days(application_data_v1.date_of_birth, 1.5)"),
+            &mut input,
+        ) {
+            NodeResult::Err(msg) => assert!(msg.contains("whole number"), "{}", msg),
+            other => panic!("expected an error, got {:?}", other),
         }
     }
+
+    #[test]
+    fn add_days_out_of_range_is_an_error() {
+        let mut input = dataset(json!({"date_of_birth": "1990-01-01"}));
+        for days in &["9999999999999", "-9999999999999", "2147483647", "100000000"] {
+            match eval_with(
+                Value::from(format!(
+                    "add_days(application_data_v1.date_of_birth, {})",
+                    days
+                )),
+                &mut input,
+            ) {
+                NodeResult::Err(msg) => assert!(msg.contains("overflowed"), "{}", msg),
+                other => panic!("expected an error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn add_days_out_of_range_fails_validation_when_folded() {
+        let strategy = rule_strategy(Value::from(
+            "add_days(date(\"2018-01-01\"), 9999999999999) > date(\"2018-01-01\")",
+        ));
+        assert!(!validate_strategy(&strategy).is_empty());
+    }
 }
@@ -1,4 +1,6 @@
 use decisionengine::EvalResult;
+use serde_json::Value;
+use std::collections::BTreeMap;
 
 trait ResultAggregate {
     fn set_result(&mut self, result: EvalResult);
@@ -16,13 +18,32 @@ pub struct DecisionResult {
 pub enum SubmoduleResult {
     ModuleResult(ModuleResult),
     RuleResult(RuleResult),
+    FailedResult(FailedResult),
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct ModuleResult {
     pub result: EvalResult,
     pub module_id: String,
+    pub module_type: String,
     pub submodule_results: Vec<SubmoduleResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<i32>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub characteristic_results: Vec<CharacteristicResult>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub variables: BTreeMap<String, Value>,
+}
+
+impl SubmoduleResult {
+    /// The result of the module or rule, or `None` if it failed.
+    pub fn result(&self) -> Option<&EvalResult> {
+        match self {
+            SubmoduleResult::ModuleResult(res) => Some(&res.result),
+            SubmoduleResult::RuleResult(res) => Some(&res.result),
+            SubmoduleResult::FailedResult(_) => None,
+        }
+    }
 }
 
 impl ModuleResult {
@@ -31,8 +52,65 @@ impl ModuleResult {
     }
 }
 
+/// A module or rule that failed to evaluate after the result of its parent
+/// was decided. It was only evaluated for the detailed result, so its failure
+/// does not fail the decision.
+#[derive(Serialize, Deserialize)]
+pub struct FailedResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<i32>,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CharacteristicResult {
+    pub characteristic_name: String,
+    pub points: i32,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RuleResult {
     pub result: EvalResult,
     pub rule_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub conditions: Vec<ConditionTrace>,
+}
+
+/// A condition evaluated on the path through a rule, in the order they ran.
+/// `lvalue` and `rvalue` are the operands of the condition's top level
+/// operation, if it is a binary operation. Operands the operation did not
+/// evaluate, such as the rvalue of `false && ...` or of an array operation,
+/// are left out.
+#[derive(Serialize, Deserialize)]
+pub struct ConditionTrace {
+    pub condition_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lvalue: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rvalue: Option<Value>,
+    pub value: Value,
+    /// The branch taken: `"true"`, `"false"` or `"on_missing"`.
+    pub branch: String,
+}
+
+/// Why a decision was reached. Rules report their `rule_id`; scorecards
+/// report their `module_id` and the cut-off they reached.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Reason {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_id: Option<String>,
+    pub result: EvalResult,
+    pub reason_code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
@@ -1,12 +1,15 @@
 use decisionengine::datasource::DecisionDataset;
-use decisionengine::visitor::DecisionTreeVisitor;
-use std::collections::HashMap;
+use decisionengine::deserializers::{get_array, get_i64, get_str};
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::results::{ConditionTrace, Reason, RuleResult, SubmoduleResult};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 
 extern crate serde_json;
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 use decisionengine::nodes::*;
-use decisionengine::{EvalResult, Evaluatable};
+use decisionengine::{EvalMode, EvalResult, Evaluatable};
 
 pub struct Rule {
     pub rule_id: i32,
@@ -14,105 +17,576 @@ pub struct Rule {
     pub conditions: HashMap<i32, Condition>,
 }
 
-impl Evaluatable for Rule {
-    fn eval(&mut self, input: &mut DecisionDataset) -> EvalResult {
+impl Rule {
+    pub fn eval_with_reason(
+        &self,
+        input: &mut DecisionDataset,
+    ) -> Result<(EvalResult, Option<Reason>), DecisionEngineError> {
+        self.run(input, None)
+    }
+
+    /// Evaluates the rule, also returning the conditions on the path taken
+    /// through it.
+    pub fn eval_traced(
+        &self,
+        input: &mut DecisionDataset,
+    ) -> Result<(EvalResult, Option<Reason>, Vec<ConditionTrace>), DecisionEngineError> {
+        let mut traces = Vec::new();
+        let (result, reason) = self.run(input, Some(&mut traces))?;
+        Ok((result, reason, traces))
+    }
+
+    fn run(
+        &self,
+        input: &mut DecisionDataset,
+        mut traces: Option<&mut Vec<ConditionTrace>>,
+    ) -> Result<(EvalResult, Option<Reason>), DecisionEngineError> {
+        let rule_id = self.rule_id;
         let mut curr_condition_id = 1;
         loop {
-            let result = match self.conditions.get_mut(&curr_condition_id) {
-                Some(mut condition) => condition.eval(input),
-                _ => panic!("Condition not found."),
+            input.step()?;
+            let result = match self.conditions.get(&curr_condition_id) {
+                Some(condition) => match traces {
+                    Some(ref mut traces) => {
+                        let (result, trace) = condition.eval_traced(input)?;
+                        traces.push(trace);
+                        result
+                    }
+                    None => condition.eval(input)?,
+                },
+                _ => {
+                    return Err(DecisionEngineError::Evaluation(format!(
+                        "Condition {} not found in rule {}.",
+                        curr_condition_id, rule_id
+                    )))
+                }
             };
             match result {
-                ConditionResult::Accept => {
-                    return EvalResult::Accept;
-                }
-                ConditionResult::Reject => {
-                    return EvalResult::Reject;
+                ConditionResult::Return(result, reason) => {
+                    return Ok((
+                        result.clone(),
+                        reason.as_ref().map(|r| Reason {
+                            rule_id: Some(rule_id),
+                            module_id: None,
+                            result: result.clone(),
+                            reason_code: r.reason_code.clone(),
+                            message: r.message.clone(),
+                        }),
+                    ));
                 }
                 &ConditionResult::Condition(condition_id) => curr_condition_id = condition_id,
             }
         }
     }
 
-    fn accept<V: DecisionTreeVisitor>(&mut self, visitor: &mut V) {
-        visitor.visit_rule(self);
-        visitor.leave_rule(self);
+    /// Canonical JSON form of the rule, with its conditions in id order.
+    pub fn serialize(&self) -> Value {
+        let mut ids: Vec<&i32> = self.conditions.keys().collect();
+        ids.sort();
+        let mut rule = Map::new();
+        rule.insert(String::from("type"), Value::from("rule"));
+        rule.insert(String::from("rule_id"), Value::from(self.rule_id));
+        rule.insert(
+            String::from("rule_name"),
+            Value::from(self.rule_name.as_str()),
+        );
+        rule.insert(
+            String::from("conditions"),
+            Value::Array(
+                ids.iter()
+                    .map(|id| self.conditions[id].serialize())
+                    .collect(),
+            ),
+        );
+        Value::Object(rule)
+    }
+}
+
+impl Evaluatable for Rule {
+    fn eval(&self, input: &mut DecisionDataset) -> Result<EvalResult, DecisionEngineError> {
+        let (result, reason) = self.eval_with_reason(input)?;
+        if let Some(reason) = reason {
+            input.add_reason(reason);
+        }
+        Ok(result)
+    }
+
+    fn eval_detailed(
+        &self,
+        input: &mut DecisionDataset,
+        _mode: EvalMode,
+    ) -> Result<SubmoduleResult, DecisionEngineError> {
+        let (result, reason, conditions) = self.eval_traced(input)?;
+        let detailed = RuleResult {
+            rule_id: self.rule_id,
+            result: result,
+            reason_code: reason.as_ref().map(|r| r.reason_code.clone()),
+            message: reason.as_ref().and_then(|r| r.message.clone()),
+            conditions: conditions,
+        };
+        if let Some(reason) = reason {
+            input.add_reason(reason);
+        }
+        Ok(SubmoduleResult::RuleResult(detailed))
+    }
+}
+
+pub struct ReturnReason {
+    pub reason_code: String,
+    pub message: Option<String>,
+}
+
+impl ReturnReason {
+    pub fn serialize_into(&self, json: &mut Map<String, Value>) {
+        json.insert(
+            String::from("reason_code"),
+            Value::from(self.reason_code.as_str()),
+        );
+        if let Some(ref message) = self.message {
+            json.insert(String::from("message"), Value::from(message.as_str()));
+        }
     }
 }
 
 enum ConditionResult {
-    Accept,
-    Reject,
+    Return(EvalResult, Option<ReturnReason>),
     Condition(i32),
 }
 
+impl ConditionResult {
+    fn serialize(&self) -> Value {
+        let mut decision = Map::new();
+        match self {
+            ConditionResult::Return(result, reason) => {
+                decision.insert(String::from("type"), Value::from("return"));
+                decision.insert(String::from("value"), serialize_eval_result(result));
+                if let Some(reason) = reason {
+                    reason.serialize_into(&mut decision);
+                }
+            }
+            ConditionResult::Condition(condition_id) => {
+                decision.insert(String::from("type"), Value::from("goto"));
+                decision.insert(String::from("value"), Value::from(condition_id.to_string()));
+            }
+        }
+        Value::Object(decision)
+    }
+}
+
 pub struct Condition {
     pub condition_id: i32,
     node: Box<EvalNode>,
     if_true: ConditionResult,
     if_false: ConditionResult,
+    /// Branch taken when the condition evaluates to null.
+    if_missing: Option<ConditionResult>,
 }
 
 impl Condition {
-    fn eval(&mut self, input: &mut DecisionDataset) -> &ConditionResult {
-        match self.node.eval(input) {
-            NodeResult::Boolean(b) => if b {
-                &self.if_true
-            } else {
-                &self.if_false
+    fn eval(&self, input: &mut DecisionDataset) -> Result<&ConditionResult, DecisionEngineError> {
+        let value = self.node.eval(input);
+        self.branch(value).map(|(_, result)| result)
+    }
+
+    fn eval_traced(
+        &self,
+        input: &mut DecisionDataset,
+    ) -> Result<(&ConditionResult, ConditionTrace), DecisionEngineError> {
+        let traced = self.node.eval_traced(input);
+        let json = traced.value.to_json();
+        let (branch, result) = self.branch(traced.value)?;
+        Ok((
+            result,
+            ConditionTrace {
+                condition_id: self.condition_id,
+                lvalue: traced.lvalue.as_ref().map(NodeResult::to_json),
+                rvalue: traced.rvalue.as_ref().map(NodeResult::to_json),
+                value: json,
+                branch: String::from(branch),
+            },
+        ))
+    }
+
+    /// The branch taken for the value of the condition, named by its key in
+    /// the condition's JSON.
+    fn branch(
+        &self,
+        value: NodeResult,
+    ) -> Result<(&'static str, &ConditionResult), DecisionEngineError> {
+        match value {
+            NodeResult::Boolean(true) => Ok(("true", &self.if_true)),
+            NodeResult::Boolean(false) => Ok(("false", &self.if_false)),
+            NodeResult::Null => match self.if_missing {
+                Some(ref if_missing) => Ok(("on_missing", if_missing)),
+                None => Err(DecisionEngineError::Evaluation(format!(
+                    "Condition {} evaluated to null and has no on_missing branch.",
+                    self.condition_id
+                ))),
             },
-            NodeResult::Err(msg) => panic!(msg),
-            _ => panic!("Top level node in condition must return bool."),
+            NodeResult::Err(msg) => Err(DecisionEngineError::Evaluation(msg)),
+            _ => Err(DecisionEngineError::Evaluation(format!(
+                "Top level node in condition {} must return bool.",
+                self.condition_id
+            ))),
+        }
+    }
+
+    fn gotos(&self) -> Vec<i32> {
+        let mut gotos = Vec::new();
+        let branches = [
+            Some(&self.if_true),
+            Some(&self.if_false),
+            self.if_missing.as_ref(),
+        ];
+        for result in branches.iter().filter_map(|result| *result) {
+            if let ConditionResult::Condition(id) = *result {
+                gotos.push(id);
+            }
+        }
+        gotos
+    }
+
+    pub fn deserialize(value: &Value) -> Result<Self, DecisionEngineError> {
+        deserialize_condition(value)
+    }
+
+    pub fn serialize(&self) -> Value {
+        let mut condition = Map::new();
+        condition.insert(String::from("type"), Value::from("condition"));
+        condition.insert(
+            String::from("condition_id"),
+            Value::from(self.condition_id.to_string()),
+        );
+        condition.insert(String::from("condition"), self.node.serialize());
+        condition.insert(String::from("true"), self.if_true.serialize());
+        condition.insert(String::from("false"), self.if_false.serialize());
+        if let Some(ref if_missing) = self.if_missing {
+            condition.insert(String::from("on_missing"), if_missing.serialize());
+        }
+        Value::Object(condition)
+    }
+}
+
+/// Finds a cycle in a graph of edges, such as the gotos between the
+/// conditions of a rule, returning the nodes along it.
+pub fn find_cycle<T: Clone + Eq + Hash + Ord>(edges: &HashMap<T, Vec<T>>) -> Option<Vec<T>> {
+    fn visit<T: Clone + Eq + Hash + Ord>(
+        id: &T,
+        edges: &HashMap<T, Vec<T>>,
+        path: &mut Vec<T>,
+        done: &mut HashSet<T>,
+    ) -> Option<Vec<T>> {
+        if let Some(start) = path.iter().position(|p| p == id) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(id.clone());
+            return Some(cycle);
+        }
+        if done.contains(id) {
+            return None;
+        }
+        path.push(id.clone());
+        for target in edges.get(id).map_or(&[][..], |targets| &targets[..]) {
+            if let Some(cycle) = visit(target, edges, path, done) {
+                return Some(cycle);
+            }
         }
+        path.pop();
+        done.insert(id.clone());
+        None
     }
 
-    pub fn deserialize(value: &Value) -> Self {
-        let (node, _) = deserialize_node(&value["condition"]);
-        Condition {
-            condition_id: value["condition_id"]
-                .as_str()
-                .unwrap()
-                .parse::<i32>()
-                .unwrap(),
-            node: node,
-            if_true: deserialize_condition_decision(&value["true"]),
-            if_false: deserialize_condition_decision(&value["false"]),
+    let mut ids: Vec<&T> = edges.keys().collect();
+    ids.sort();
+    let mut done = HashSet::new();
+    for id in ids {
+        if let Some(cycle) = visit(id, edges, &mut Vec::new(), &mut done) {
+            return Some(cycle);
         }
     }
+    None
 }
 
-pub fn deserialize_rule(v: &Value) -> Rule {
+pub fn format_goto_cycle(cycle: &[i32]) -> String {
+    let ids: Vec<String> = cycle.iter().map(|id| id.to_string()).collect();
+    format!("goto cycle between conditions {}.", ids.join(" -> "))
+}
+
+pub fn deserialize_rule(v: &Value) -> Result<Rule, DecisionEngineError> {
     let mut conditions = HashMap::new();
-    for condition in v["conditions"].as_array().unwrap() {
-        let r = deserialize_condition(condition);
+    for condition in get_array(v, "conditions")? {
+        let r = deserialize_condition(condition)?;
         conditions.insert(r.condition_id, r);
     }
 
-    Rule {
-        rule_name: v["rule_name"].as_str().unwrap().to_string(),
-        rule_id: v["rule_id"].as_i64().unwrap() as i32,
+    let gotos: HashMap<i32, Vec<i32>> = conditions
+        .values()
+        .map(|c: &Condition| (c.condition_id, c.gotos()))
+        .collect();
+    if let Some(cycle) = find_cycle(&gotos) {
+        return Err(DecisionEngineError::MalformedStrategy(format_goto_cycle(
+            &cycle,
+        )));
+    }
+
+    Ok(Rule {
+        rule_name: get_str(v, "rule_name")?.to_string(),
+        rule_id: get_i64(v, "rule_id")? as i32,
         conditions: conditions,
+    })
+}
+
+pub fn parse_eval_result(value: &str) -> Option<EvalResult> {
+    match value {
+        "ACCEPT" => Some(EvalResult::Accept),
+        "REFER" => Some(EvalResult::Refer),
+        "REJECT" => Some(EvalResult::Reject),
+        _ => None,
     }
 }
 
-fn deserialize_condition_decision(v: &Value) -> ConditionResult {
-    match v["type"].as_str().unwrap() {
-        "return" => match v["value"].as_str().unwrap() {
-            "ACCEPT" => ConditionResult::Accept,
-            "REJECT" => ConditionResult::Reject,
-            _ => panic!("Unknown condition decision."),
-        },
-        "goto" => ConditionResult::Condition(v["value"].as_str().unwrap().parse::<i32>().unwrap()),
-        _ => panic!("Unknown condition decision."),
+pub fn serialize_eval_result(result: &EvalResult) -> Value {
+    Value::from(match result {
+        EvalResult::Accept => "ACCEPT",
+        EvalResult::Refer => "REFER",
+        EvalResult::Reject => "REJECT",
+    })
+}
+
+pub fn deserialize_eval_result(v: &Value) -> Result<EvalResult, DecisionEngineError> {
+    v.as_str()
+        .and_then(parse_eval_result)
+        .ok_or_else(|| DecisionEngineError::MalformedStrategy(format!("Unknown outcome {}.", v)))
+}
+
+pub fn deserialize_return_reason(v: &Value) -> Option<ReturnReason> {
+    match v["reason_code"].as_str() {
+        Some(reason_code) => Some(ReturnReason {
+            reason_code: reason_code.to_string(),
+            message: v["message"].as_str().map(|m| m.to_string()),
+        }),
+        None => None,
+    }
+}
+
+fn deserialize_condition_id(v: &Value) -> Result<i32, DecisionEngineError> {
+    v.as_str()
+        .and_then(|id| id.parse::<i32>().ok())
+        .ok_or_else(|| {
+            DecisionEngineError::MalformedStrategy(format!(
+                "Expected condition id to be an integer string, got {}.",
+                v
+            ))
+        })
+}
+
+fn deserialize_condition_decision(v: &Value) -> Result<ConditionResult, DecisionEngineError> {
+    match get_str(v, "type")? {
+        "return" => Ok(ConditionResult::Return(
+            deserialize_eval_result(&v["value"])?,
+            deserialize_return_reason(v),
+        )),
+        "goto" => Ok(ConditionResult::Condition(deserialize_condition_id(
+            &v["value"],
+        )?)),
+        _ => Err(DecisionEngineError::MalformedStrategy(String::from(
+            "Unknown condition decision.",
+        ))),
     }
 }
 
-pub fn deserialize_condition(v: &Value) -> Condition {
-    let (node, _) = deserialize_node(&v["condition"]);
-    Condition {
-        condition_id: v["condition_id"].as_str().unwrap().parse::<i32>().unwrap(),
+pub fn deserialize_condition(v: &Value) -> Result<Condition, DecisionEngineError> {
+    let (node, _) = deserialize_node(&v["condition"])?;
+    Ok(Condition {
+        condition_id: deserialize_condition_id(&v["condition_id"])?,
         node: node,
-        if_true: deserialize_condition_decision(&v["true"]),
-        if_false: deserialize_condition_decision(&v["false"]),
+        if_true: deserialize_condition_decision(&v["true"])?,
+        if_false: deserialize_condition_decision(&v["false"])?,
+        if_missing: match v["on_missing"] {
+            Value::Null => None,
+            ref on_missing => Some(deserialize_condition_decision(on_missing)?),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decisionengine::testing::{dataset, detailed, rule_strategy};
+    use decisionengine::validation::validate_strategy;
+    use decisionengine::{deserialize_strategy, EvalMode, EvalResult, Evaluatable};
+    use serde_json::Value;
+
+    fn goto(condition_id: i32) -> Value {
+        json!({"type": "goto", "value": condition_id.to_string()})
+    }
+
+    /// Rule whose conditions each go to the next, or to `last_goto` from
+    /// the last, accepting once they run out.
+    fn chain(conditions: i32, last_goto: Option<i32>) -> Value {
+        let conditions: Vec<Value> = (1..conditions + 1)
+            .map(|id| {
+                let next = if id < conditions {
+                    goto(id + 1)
+                } else {
+                    last_goto.map_or(json!({"type": "return", "value": "ACCEPT"}), goto)
+                };
+                json!({
+                    "type": "condition",
+                    "condition_id": id.to_string(),
+                    "condition": "true",
+                    "true": next,
+                    "false": {"type": "return", "value": "REJECT"},
+                })
+            })
+            .collect();
+        let mut strategy = rule_strategy(Value::from("true"));
+        strategy["children"][0]["conditions"] = Value::Array(conditions);
+        strategy
+    }
+
+    #[test]
+    fn find_cycle_returns_the_nodes_along_it() {
+        let mut edges = HashMap::new();
+        edges.insert(1, vec![2, 3]);
+        edges.insert(2, vec![3]);
+        edges.insert(3, vec![]);
+        assert_eq!(find_cycle(&edges), None);
+        edges.insert(3, vec![2]);
+        assert_eq!(find_cycle(&edges), Some(vec![2, 3, 2]));
+    }
+
+    #[test]
+    fn goto_cycles_are_rejected() {
+        let strategy = chain(3, Some(2));
+        match deserialize_strategy(&strategy) {
+            Err(DecisionEngineError::MalformedStrategy(msg)) => {
+                assert_eq!(msg, "goto cycle between conditions 2 -> 3 -> 2.")
+            }
+            _ => panic!("expected a goto cycle"),
+        }
+        let errors = validate_strategy(&strategy);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].pointer, "/children/0/conditions");
+        assert_eq!(
+            errors[0].message,
+            "goto cycle between conditions 2 -> 3 -> 2."
+        );
+    }
+
+    #[test]
+    fn each_condition_evaluated_takes_a_step() {
+        let strategy = deserialize_strategy(&chain(3, None)).unwrap();
+        let mut input = dataset(json!({})).with_step_budget(3);
+        assert_eq!(strategy.eval(&mut input).unwrap(), EvalResult::Accept);
+        assert_eq!(input.steps(), 3);
+
+        let mut input = dataset(json!({})).with_step_budget(2);
+        match strategy.eval(&mut input) {
+            Err(DecisionEngineError::StepBudgetExceeded(2)) => {}
+            _ => panic!("expected the step budget to be exceeded"),
+        }
+    }
+
+    fn trace(condition: &str) -> Value {
+        let strategy = rule_strategy(Value::from(condition));
+        let details = detailed(&strategy, json!({}), EvalMode::Exhaustive).unwrap();
+        details["submodule_results"][0]["conditions"][0].clone()
+    }
+
+    #[test]
+    fn null_conditions_take_the_on_missing_branch() {
+        let mut strategy = rule_strategy(Value::from("application_data_v1.income > 1000"));
+        strategy["children"][0]["conditions"][0]["on_missing"] =
+            json!({"type": "return", "value": "REFER", "reason_code": "NO_INCOME"});
+        let details = detailed(&strategy, json!({}), EvalMode::ShortCircuit).unwrap();
+        assert_eq!(details["result"], "Refer");
+        assert_eq!(details["submodule_results"][0]["reason_code"], "NO_INCOME");
+        assert_eq!(
+            details["submodule_results"][0]["conditions"][0]["branch"],
+            "on_missing"
+        );
+
+        let details = detailed(&strategy, json!({"income": 2000}), EvalMode::ShortCircuit).unwrap();
+        assert_eq!(details["result"], "Accept");
+    }
+
+    #[test]
+    fn rule_reports_the_reason_of_its_outcome() {
+        let mut strategy = rule_strategy(Value::from("application_data_v1.age >= 40"));
+        strategy["children"][0]["conditions"][0]["false"]["message"] = json!("Too young.");
+        let decision_strategy = deserialize_strategy(&strategy).unwrap();
+        let mut input = dataset(json!({}));
+        assert_eq!(
+            decision_strategy.eval(&mut input).unwrap(),
+            EvalResult::Reject
+        );
+        let reasons = serde_json::to_value(input.reasons()).unwrap();
+        assert_eq!(
+            reasons,
+            json!([{
+                "rule_id": 1,
+                "result": "Reject",
+                "reason_code": "DECLINED",
+                "message": "Too young.",
+            }])
+        );
+
+        let details = detailed(&strategy, json!({}), EvalMode::ShortCircuit).unwrap();
+        assert_eq!(details["submodule_results"][0]["reason_code"], "DECLINED");
+        assert_eq!(details["submodule_results"][0]["message"], "Too young.");
+    }
+
+    #[test]
+    fn outcomes_without_a_reason_code_report_none() {
+        let decision_strategy =
+            deserialize_strategy(&rule_strategy(Value::from("application_data_v1.age >= 18")))
+                .unwrap();
+        let mut input = dataset(json!({}));
+        assert_eq!(
+            decision_strategy.eval(&mut input).unwrap(),
+            EvalResult::Accept
+        );
+        assert!(input.reasons().is_empty());
+    }
+
+    #[test]
+    fn trace_records_operands_and_branch() {
+        assert_eq!(
+            trace("application_data_v1.age >= 18"),
+            json!({
+                "condition_id": 1,
+                "lvalue": 30,
+                "rvalue": 18,
+                "value": true,
+                "branch": "true",
+            })
+        );
+    }
+
+    #[test]
+    fn trace_leaves_out_operands_that_were_not_evaluated() {
+        // The rvalue divides by zero, but `&&` never evaluates it.
+        assert_eq!(
+            trace("application_data_v1.age < 18 && 1 / (application_data_v1.age - 30) > 0"),
+            json!({
+                "condition_id": 1,
+                "lvalue": false,
+                "value": false,
+                "branch": "false",
+            })
+        );
+    }
+
+    #[test]
+    fn trace_of_array_operations_leaves_out_the_rvalue() {
+        assert_eq!(
+            trace("any([1, 2, 30], element == application_data_v1.age)"),
+            json!({
+                "condition_id": 1,
+                "lvalue": [1, 2, 30],
+                "value": true,
+                "branch": "true",
+            })
+        );
     }
 }
@@ -4,6 +4,11 @@ table! {
         decision_strategy_id -> Int4,
         application_data -> Jsonb,
         result -> Nullable<Jsonb>,
+        decided_at -> Timestamptz,
+        decision_strategy_hash -> Varchar,
+        bureau_data -> Jsonb,
+        outcome -> Varchar,
+        details -> Jsonb,
     }
 }
 
@@ -11,6 +16,8 @@ table! {
     decision_strategy (decision_strategy_id) {
         decision_strategy_id -> Int4,
         decision_strategy_json -> Jsonb,
+        created_at -> Timestamptz,
+        retired_at -> Nullable<Timestamptz>,
     }
 }
 
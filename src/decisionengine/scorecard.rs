@@ -0,0 +1,445 @@
+extern crate serde_json;
+
+use decisionengine::datasource::DecisionDataset;
+use decisionengine::deserializers::{get_array, get_i32, get_str};
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::modules::{module_json, module_result, Module};
+use decisionengine::nodes::{
+    decimal_to_json, deserialize_node, parse_decimal, EvalNode, NodeResult,
+};
+use decisionengine::results::{CharacteristicResult, Reason, SubmoduleResult};
+use decisionengine::rules::{
+    deserialize_eval_result, deserialize_return_reason, serialize_eval_result, ReturnReason,
+};
+use decisionengine::{EvalMode, EvalResult, Evaluatable};
+use rust_decimal::Decimal;
+use serde_json::{Map, Value};
+
+/// Reason code reported for a cut-off, or the default, without its own.
+const CUTOFF_REASON_CODE: &str = "SCORECARD_CUTOFF";
+
+/// Sums the points of every characteristic and maps the total to a result
+/// using the highest cut-off it reaches. The outcome is recorded as a reason
+/// under the cut-off's `reason_code`, or the default's `default_reason_code`.
+pub struct ScorecardModule {
+    pub module_name: String,
+    pub characteristics: Vec<Characteristic>,
+    cutoffs: Vec<Cutoff>,
+    default: EvalResult,
+    default_reason: Option<ReturnReason>,
+}
+
+/// Scores a value by the first bin containing it. Values outside every bin,
+/// or missing from the dataset, score `default_points`.
+pub struct Characteristic {
+    pub characteristic_name: String,
+    node: Box<EvalNode>,
+    bins: Vec<Bin>,
+    default_points: i32,
+}
+
+/// Range of values worth `points`. `min` is inclusive and `max` exclusive;
+/// a missing bound is unbounded.
+struct Bin {
+    min: Option<Decimal>,
+    max: Option<Decimal>,
+    points: i32,
+}
+
+struct Cutoff {
+    min_score: i32,
+    result: EvalResult,
+    reason: Option<ReturnReason>,
+}
+
+impl Bin {
+    fn contains(&self, value: Decimal) -> bool {
+        self.min.map_or(true, |min| value >= min) && self.max.map_or(true, |max| value < max)
+    }
+
+    fn serialize(&self) -> Value {
+        let mut bin = Map::new();
+        if let Some(ref min) = self.min {
+            bin.insert(String::from("min"), decimal_to_json(min));
+        }
+        if let Some(ref max) = self.max {
+            bin.insert(String::from("max"), decimal_to_json(max));
+        }
+        bin.insert(String::from("points"), Value::from(self.points));
+        Value::Object(bin)
+    }
+}
+
+impl Characteristic {
+    pub fn points(&self, input: &mut DecisionDataset) -> Result<i32, DecisionEngineError> {
+        let value = match self.node.eval(input) {
+            NodeResult::Numeric(n) => n,
+            NodeResult::Null => return Ok(self.default_points),
+            NodeResult::Err(msg) => return Err(DecisionEngineError::Evaluation(msg)),
+            _ => {
+                return Err(DecisionEngineError::Evaluation(format!(
+                    "Scorecard characteristic {} must return a number.",
+                    self.characteristic_name
+                )))
+            }
+        };
+        Ok(match self.bins.iter().find(|bin| bin.contains(value)) {
+            Some(bin) => bin.points,
+            None => self.default_points,
+        })
+    }
+
+    fn serialize(&self) -> Value {
+        let mut characteristic = Map::new();
+        characteristic.insert(
+            String::from("characteristic_name"),
+            Value::from(self.characteristic_name.as_str()),
+        );
+        characteristic.insert(String::from("value"), self.node.serialize());
+        characteristic.insert(
+            String::from("bins"),
+            Value::Array(self.bins.iter().map(Bin::serialize).collect()),
+        );
+        if self.default_points != 0 {
+            characteristic.insert(
+                String::from("default_points"),
+                Value::from(self.default_points),
+            );
+        }
+        Value::Object(characteristic)
+    }
+}
+
+impl ScorecardModule {
+    pub fn score(
+        &self,
+        input: &mut DecisionDataset,
+    ) -> Result<(i32, Vec<CharacteristicResult>), DecisionEngineError> {
+        let mut total: i32 = 0;
+        let mut characteristic_results = Vec::new();
+        for characteristic in &self.characteristics {
+            let points = characteristic.points(input)?;
+            total = total.checked_add(points).ok_or_else(|| {
+                DecisionEngineError::Evaluation(format!(
+                    "Scorecard {} score overflowed.",
+                    self.module_name
+                ))
+            })?;
+            characteristic_results.push(CharacteristicResult {
+                characteristic_name: characteristic.characteristic_name.clone(),
+                points: points,
+            });
+        }
+        Ok((total, characteristic_results))
+    }
+
+    fn result(&self, score: i32) -> (EvalResult, Reason) {
+        let (result, reason, message) =
+            match self.cutoffs.iter().find(|cutoff| score >= cutoff.min_score) {
+                Some(cutoff) => (
+                    &cutoff.result,
+                    cutoff.reason.as_ref(),
+                    format!(
+                        "Score {} reached the cut-off of {}.",
+                        score, cutoff.min_score
+                    ),
+                ),
+                None => (
+                    &self.default,
+                    self.default_reason.as_ref(),
+                    format!("Score {} is below every cut-off.", score),
+                ),
+            };
+        let reason = Reason {
+            rule_id: None,
+            module_id: Some(self.module_name.clone()),
+            result: result.clone(),
+            reason_code: reason.map_or(String::from(CUTOFF_REASON_CODE), |r| r.reason_code.clone()),
+            message: Some(reason.and_then(|r| r.message.clone()).unwrap_or(message)),
+        };
+        (result.clone(), reason)
+    }
+
+    /// Canonical JSON form of the scorecard, with its cut-offs from highest
+    /// to lowest.
+    pub fn serialize(&self) -> Value {
+        let mut json = module_json(self);
+        json.insert(
+            String::from("characteristics"),
+            Value::Array(
+                self.characteristics
+                    .iter()
+                    .map(Characteristic::serialize)
+                    .collect(),
+            ),
+        );
+        json.insert(
+            String::from("cutoffs"),
+            Value::Array(
+                self.cutoffs
+                    .iter()
+                    .map(|cutoff| {
+                        let mut json = Map::new();
+                        json.insert(String::from("min_score"), Value::from(cutoff.min_score));
+                        json.insert(
+                            String::from("result"),
+                            serialize_eval_result(&cutoff.result),
+                        );
+                        if let Some(ref reason) = cutoff.reason {
+                            reason.serialize_into(&mut json);
+                        }
+                        Value::Object(json)
+                    })
+                    .collect(),
+            ),
+        );
+        json.insert(
+            String::from("default"),
+            serialize_eval_result(&self.default),
+        );
+        if let Some(ref reason) = self.default_reason {
+            json.insert(
+                String::from("default_reason_code"),
+                Value::from(reason.reason_code.as_str()),
+            );
+            if let Some(ref message) = reason.message {
+                json.insert(
+                    String::from("default_message"),
+                    Value::from(message.as_str()),
+                );
+            }
+        }
+        Value::Object(json)
+    }
+}
+
+impl Module for ScorecardModule {
+    fn module_name(&self) -> &str {
+        &self.module_name
+    }
+
+    fn module_type(&self) -> &'static str {
+        "scorecard"
+    }
+}
+
+impl Evaluatable for ScorecardModule {
+    fn eval(&self, input: &mut DecisionDataset) -> Result<EvalResult, DecisionEngineError> {
+        let (score, _) = self.score(input)?;
+        let (result, reason) = self.result(score);
+        input.add_reason(reason);
+        Ok(result)
+    }
+
+    fn eval_detailed(
+        &self,
+        input: &mut DecisionDataset,
+        _mode: EvalMode,
+    ) -> Result<SubmoduleResult, DecisionEngineError> {
+        let (score, characteristic_results) = self.score(input)?;
+        let (result, reason) = self.result(score);
+        input.add_reason(reason);
+        let mut detailed = module_result(self, result, Vec::new());
+        detailed.score = Some(score);
+        detailed.characteristic_results = characteristic_results;
+        Ok(SubmoduleResult::ModuleResult(detailed))
+    }
+}
+
+fn deserialize_bound(v: &Value) -> Result<Option<Decimal>, DecisionEngineError> {
+    match v {
+        Value::Null => Ok(None),
+        Value::Number(n) => match parse_decimal(n) {
+            Some(bound) => Ok(Some(bound)),
+            None => Err(DecisionEngineError::MalformedStrategy(format!(
+                "Expected numeric bin bound, got {}.",
+                v
+            ))),
+        },
+        _ => Err(DecisionEngineError::MalformedStrategy(format!(
+            "Expected numeric bin bound, got {}.",
+            v
+        ))),
+    }
+}
+
+fn deserialize_bin(v: &Value) -> Result<Bin, DecisionEngineError> {
+    Ok(Bin {
+        min: deserialize_bound(&v["min"])?,
+        max: deserialize_bound(&v["max"])?,
+        points: get_i32(v, "points")?,
+    })
+}
+
+fn deserialize_characteristic(v: &Value) -> Result<Characteristic, DecisionEngineError> {
+    let (node, _) = deserialize_node(&v["value"])?;
+    let mut bins = Vec::new();
+    for bin in get_array(v, "bins")? {
+        bins.push(deserialize_bin(bin)?);
+    }
+    Ok(Characteristic {
+        characteristic_name: get_str(v, "characteristic_name")?.to_string(),
+        node: node,
+        bins: bins,
+        default_points: match v["default_points"] {
+            Value::Null => 0,
+            _ => get_i32(v, "default_points")?,
+        },
+    })
+}
+
+pub fn deserialize_scorecard_module(v: &Value) -> Result<ScorecardModule, DecisionEngineError> {
+    let mut cutoffs = Vec::new();
+    for cutoff in get_array(v, "cutoffs")? {
+        cutoffs.push(Cutoff {
+            min_score: get_i32(cutoff, "min_score")?,
+            result: deserialize_eval_result(&cutoff["result"])?,
+            reason: deserialize_return_reason(cutoff),
+        });
+    }
+    cutoffs.sort_by(|a, b| b.min_score.cmp(&a.min_score));
+
+    let mut characteristics = Vec::new();
+    for characteristic in get_array(v, "characteristics")? {
+        characteristics.push(deserialize_characteristic(characteristic)?);
+    }
+
+    Ok(ScorecardModule {
+        module_name: get_str(v, "module_name")?.to_string(),
+        characteristics: characteristics,
+        cutoffs: cutoffs,
+        default: deserialize_eval_result(&v["default"])?,
+        default_reason: v["default_reason_code"]
+            .as_str()
+            .map(|reason_code| ReturnReason {
+                reason_code: reason_code.to_string(),
+                message: v["default_message"].as_str().map(|m| m.to_string()),
+            }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decisionengine::testing::dataset;
+    use decisionengine::validation::validate_strategy;
+
+    fn scorecard(points: i64) -> Value {
+        json!({
+            "type": "module",
+            "module_type": "scorecard",
+            "module_name": "Affordability",
+            "characteristics": [{
+                "characteristic_name": "Age",
+                "value": {"type": "input", "value": "application_data_v1.age"},
+                "bins": [{"max": 18, "points": 0}, {"min": 18, "points": points}],
+            }, {
+                "characteristic_name": "Income",
+                "value": {"type": "input", "value": "application_data_v1.income"},
+                "bins": [{"min": 20000, "points": points}],
+                "default_points": 5,
+            }],
+            "cutoffs": [
+                {"min_score": 30, "result": "ACCEPT"},
+                {"min_score": 20, "result": "REFER", "reason_code": "LOW_SCORE"},
+            ],
+            "default": "REJECT",
+        })
+    }
+
+    fn evaluate(strategy: &Value, application_data: Value) -> (EvalResult, Vec<Reason>) {
+        let module = deserialize_scorecard_module(strategy).unwrap();
+        let mut input = dataset(application_data);
+        let result = module.eval(&mut input).unwrap();
+        (result, input.reasons().clone())
+    }
+
+    #[test]
+    fn score_sums_points_of_every_characteristic() {
+        let module = deserialize_scorecard_module(&scorecard(20)).unwrap();
+        let (score, characteristics) = module.score(&mut dataset(json!({}))).unwrap();
+        assert_eq!(score, 25);
+        assert_eq!(characteristics[0].points, 20);
+        assert_eq!(characteristics[1].points, 5);
+
+        let mut input = dataset(json!({ "income": 30000 }));
+        assert_eq!(module.score(&mut input).unwrap().0, 40);
+    }
+
+    #[test]
+    fn score_overflow_is_an_error() {
+        let module = deserialize_scorecard_module(&scorecard(i64::from(i32::max_value()))).unwrap();
+        let mut input = dataset(json!({ "income": 30000 }));
+        match module.score(&mut input) {
+            Err(DecisionEngineError::Evaluation(msg)) => {
+                assert_eq!(msg, "Scorecard Affordability score overflowed.")
+            }
+            _ => panic!("expected an evaluation error"),
+        }
+    }
+
+    #[test]
+    fn points_outside_i32_are_rejected() {
+        let strategy = scorecard(i64::from(i32::max_value()) + 1);
+        assert!(deserialize_scorecard_module(&strategy).is_err());
+        let errors = validate_strategy(&strategy);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].pointer, "/characteristics/0/bins/1/points");
+
+        let mut strategy = scorecard(20);
+        strategy["cutoffs"][0]["min_score"] = json!(i64::from(i32::min_value()) - 1);
+        assert!(deserialize_scorecard_module(&strategy).is_err());
+        assert_eq!(
+            validate_strategy(&strategy)[0].pointer,
+            "/cutoffs/0/min_score"
+        );
+    }
+
+    #[test]
+    fn outcome_is_reported_as_a_reason() {
+        let (result, reasons) = evaluate(&scorecard(20), json!({}));
+        assert_eq!(result, EvalResult::Refer);
+        assert_eq!(reasons.len(), 1);
+        assert_eq!(reasons[0].rule_id, None);
+        assert_eq!(reasons[0].module_id, Some(String::from("Affordability")));
+        assert_eq!(reasons[0].result, EvalResult::Refer);
+        assert_eq!(reasons[0].reason_code, "LOW_SCORE");
+        assert_eq!(
+            reasons[0].message,
+            Some(String::from("Score 25 reached the cut-off of 20."))
+        );
+    }
+
+    #[test]
+    fn declined_scorecard_reports_a_reason() {
+        let (result, reasons) = evaluate(&scorecard(10), json!({}));
+        assert_eq!(result, EvalResult::Reject);
+        assert_eq!(reasons.len(), 1);
+        assert_eq!(reasons[0].reason_code, CUTOFF_REASON_CODE);
+        assert_eq!(
+            reasons[0].message,
+            Some(String::from("Score 15 is below every cut-off."))
+        );
+
+        let mut strategy = scorecard(10);
+        strategy["default_reason_code"] = json!("SCORE_TOO_LOW");
+        strategy["default_message"] = json!("Affordability score too low.");
+        let (_, reasons) = evaluate(&strategy, json!({}));
+        assert_eq!(reasons[0].reason_code, "SCORE_TOO_LOW");
+        assert_eq!(
+            reasons[0].message,
+            Some(String::from("Affordability score too low."))
+        );
+    }
+
+    #[test]
+    fn serialize_keeps_reasons() {
+        let mut strategy = scorecard(20);
+        strategy["default_reason_code"] = json!("SCORE_TOO_LOW");
+        let serialized = deserialize_scorecard_module(&strategy).unwrap().serialize();
+        assert_eq!(serialized["cutoffs"][1]["reason_code"], "LOW_SCORE");
+        assert_eq!(serialized["cutoffs"][0]["reason_code"], Value::Null);
+        assert_eq!(serialized["default_reason_code"], "SCORE_TOO_LOW");
+        assert!(validate_strategy(&serialized).is_empty());
+    }
+}
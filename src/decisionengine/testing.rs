@@ -0,0 +1,88 @@
+//! Helpers shared by the unit tests.
+
+use chrono::{TimeZone, Utc};
+use decisionengine::datasource::clock::FixedClock;
+use decisionengine::datasource::DecisionDataset;
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::nodes::{deserialize_node, NodeResult};
+use decisionengine::{deserialize_strategy, DecisionStrategy, EvalMode, Evaluatable};
+use serde_json::Value;
+
+/// Dataset for an applicant with the given application data, merged over a
+/// default applicant, as of 2018-08-17T12:00:00Z.
+pub fn dataset(application_data: Value) -> DecisionDataset {
+    let mut applicant = json!({
+        "first_name": "Tony",
+        "last_name": "Stark",
+        "age": 30,
+    });
+    if let Value::Object(fields) = application_data {
+        for (key, value) in fields {
+            applicant[key] = value;
+        }
+    }
+    DecisionDataset::new(serde_json::from_value(applicant).unwrap()).with_clock(Box::new(
+        FixedClock::new(Utc.ymd(2018, 8, 17).and_hms(12, 0, 0)),
+    ))
+}
+
+/// Strategy of a single rule that accepts if `condition` holds and rejects
+/// with reason code `DECLINED` otherwise.
+pub fn rule_strategy(condition: Value) -> Value {
+    json!({
+        "type": "module",
+        "module_type": "all",
+        "module_name": "Test",
+        "children": [rule(1, condition)],
+    })
+}
+
+pub fn rule(rule_id: i64, condition: Value) -> Value {
+    json!({
+        "type": "rule",
+        "rule_id": rule_id,
+        "rule_name": format!("Rule {}", rule_id),
+        "conditions": [{
+            "type": "condition",
+            "condition_id": "1",
+            "condition": condition,
+            "true": {"type": "return", "value": "ACCEPT"},
+            "false": {"type": "return", "value": "REJECT", "reason_code": "DECLINED"},
+        }],
+    })
+}
+
+/// Unretired stored strategy with id 1 holding the given JSON.
+pub fn stored(json: Value) -> DecisionStrategy {
+    DecisionStrategy {
+        decision_strategy_id: 1,
+        decision_strategy_json: json,
+        created_at: Utc::now(),
+        retired_at: None,
+    }
+}
+
+/// Evaluates a strategy in detail for an applicant, returning the detailed
+/// result as JSON.
+pub fn detailed(
+    strategy: &Value,
+    application_data: Value,
+    mode: EvalMode,
+) -> Result<Value, DecisionEngineError> {
+    let strategy = deserialize_strategy(strategy)?;
+    let details = strategy.eval_detailed(&mut dataset(application_data), mode)?;
+    Ok(serde_json::to_value(&details).unwrap())
+}
+
+/// Deserializes and evaluates a node, given in either JSON or expression
+/// form, against the default applicant.
+pub fn eval(node: Value) -> NodeResult {
+    eval_with(node, &mut dataset(json!({})))
+}
+
+pub fn eval_with(node: Value, input: &mut DecisionDataset) -> NodeResult {
+    match deserialize_node(&node) {
+        Ok((node, _)) => node.eval(input),
+        Err(error) => panic!("{}", error),
+    }
+}
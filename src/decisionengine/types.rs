@@ -0,0 +1,180 @@
+use decisionengine::nodes::NodeResult;
+use std::fmt;
+
+/// Static type of an `EvalNode`, inferred before a strategy is evaluated.
+#[derive(Clone, PartialEq, Debug)]
+pub enum NodeType {
+    Numeric,
+    Boolean,
+    Text,
+    Date,
+    DateTime,
+    Array(Box<NodeType>),
+    /// Named fields, such as a bureau account. Field types are only known
+    /// once the record is evaluated.
+    Record,
+    /// Element type of empty or mixed constant arrays, and the type of null;
+    /// compatible with every other type.
+    Any,
+}
+
+impl NodeType {
+    pub fn is_compatible(&self, other: &NodeType) -> bool {
+        match (self, other) {
+            (NodeType::Any, _) | (_, NodeType::Any) => true,
+            (NodeType::Array(l), NodeType::Array(r)) => l.is_compatible(r),
+            (l, r) => l == r,
+        }
+    }
+}
+
+impl fmt::Display for NodeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodeType::Numeric => write!(f, "Numeric"),
+            NodeType::Boolean => write!(f, "Boolean"),
+            NodeType::Text => write!(f, "Text"),
+            NodeType::Date => write!(f, "Date"),
+            NodeType::DateTime => write!(f, "DateTime"),
+            NodeType::Array(element_type) => write!(f, "Array<{}>", element_type),
+            NodeType::Record => write!(f, "Record"),
+            NodeType::Any => write!(f, "Any"),
+        }
+    }
+}
+
+pub fn type_of(value: &NodeResult) -> Result<NodeType, String> {
+    match value {
+        NodeResult::Numeric(_) => Ok(NodeType::Numeric),
+        NodeResult::Boolean(_) => Ok(NodeType::Boolean),
+        NodeResult::Text(_) => Ok(NodeType::Text),
+        NodeResult::Date(_) => Ok(NodeType::Date),
+        NodeResult::DateTime(_) => Ok(NodeType::DateTime),
+        NodeResult::Array(values) => {
+            let mut element_type: Option<NodeType> = None;
+            for value in values {
+                let t = type_of(value)?;
+                element_type = match element_type {
+                    Some(ref e) if *e != t => Some(NodeType::Any),
+                    Some(e) => Some(e),
+                    None => Some(t),
+                };
+            }
+            Ok(NodeType::Array(Box::new(
+                element_type.unwrap_or(NodeType::Any),
+            )))
+        }
+        NodeResult::Record(_) => Ok(NodeType::Record),
+        NodeResult::Null => Ok(NodeType::Any),
+        NodeResult::Err(msg) => Err(msg.clone()),
+    }
+}
+
+/// Checks the operand types of a binary operation that takes `expected` on
+/// both sides, returning `result` on success.
+pub fn check_operands(
+    op: &str,
+    lvalue: &NodeType,
+    rvalue: &NodeType,
+    expected: NodeType,
+    result: NodeType,
+) -> Result<NodeType, String> {
+    if lvalue.is_compatible(&expected) && rvalue.is_compatible(&expected) {
+        Ok(result)
+    } else {
+        Err(operands_error(op, &expected, lvalue, rvalue))
+    }
+}
+
+pub fn operands_error(
+    op: &str,
+    expected: &NodeType,
+    lvalue: &NodeType,
+    rvalue: &NodeType,
+) -> String {
+    format!(
+        "Operation {} expects ({}, {}), got ({}, {}).",
+        op, expected, expected, lvalue, rvalue
+    )
+}
+
+/// Checks the operand type of a unary operation, returning `result` on
+/// success.
+pub fn check_operand(
+    op: &str,
+    value: &NodeType,
+    expected: NodeType,
+    result: NodeType,
+) -> Result<NodeType, String> {
+    if value.is_compatible(&expected) {
+        Ok(result)
+    } else {
+        Err(operand_error(op, &expected, value))
+    }
+}
+
+pub fn operand_error(op: &str, expected: &NodeType, value: &NodeType) -> String {
+    format!("Operation {} expects ({}), got ({}).", op, expected, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decisionengine::nodes::deserialize_node;
+    use decisionengine::variables::VariableTypes;
+    use serde_json::Value;
+
+    fn node_type(expression: &str) -> Result<NodeType, String> {
+        let (node, _) = deserialize_node(&Value::from(expression)).unwrap();
+        node.node_type(&VariableTypes::new())
+    }
+
+    #[test]
+    fn operations_infer_their_result_type() {
+        assert_eq!(
+            node_type("application_data_v1.age + 1"),
+            Ok(NodeType::Numeric)
+        );
+        assert_eq!(
+            node_type("application_data_v1.age >= 18"),
+            Ok(NodeType::Boolean)
+        );
+        assert_eq!(
+            node_type("application_data_v1.first_name"),
+            Ok(NodeType::Text)
+        );
+        assert_eq!(
+            node_type("[1, 2, 3]"),
+            Ok(NodeType::Array(Box::new(NodeType::Numeric)))
+        );
+        assert_eq!(
+            node_type("[1, \"two\"]"),
+            Ok(NodeType::Array(Box::new(NodeType::Any)))
+        );
+    }
+
+    #[test]
+    fn mismatched_operands_are_rejected() {
+        assert_eq!(
+            node_type("application_data_v1.age && true"),
+            Err(String::from(
+                "Operation && expects (Boolean, Boolean), got (Numeric, Boolean)."
+            ))
+        );
+        assert_eq!(
+            node_type("!application_data_v1.first_name"),
+            Err(String::from("Operation ! expects (Boolean), got (Text)."))
+        );
+    }
+
+    #[test]
+    fn any_is_compatible_with_every_type() {
+        assert!(NodeType::Any.is_compatible(&NodeType::Date));
+        assert!(NodeType::Text.is_compatible(&NodeType::Any));
+        assert!(NodeType::Array(Box::new(NodeType::Any))
+            .is_compatible(&NodeType::Array(Box::new(NodeType::Numeric))));
+        assert!(!NodeType::Array(Box::new(NodeType::Text))
+            .is_compatible(&NodeType::Array(Box::new(NodeType::Numeric))));
+        assert!(!NodeType::Date.is_compatible(&NodeType::DateTime));
+    }
+}
@@ -0,0 +1,921 @@
+extern crate serde_json;
+
+use decisionengine::datasource::parse_input_node;
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::expressions::parse_expression;
+use decisionengine::nodes::{
+    binary_operation, deserialize_node, operation_arity, operation_error, parse_date,
+    parse_datetime, parse_decimal, ternary_operation, unary_operation, Arity,
+};
+use decisionengine::rules::{find_cycle, format_goto_cycle, parse_eval_result};
+use decisionengine::types::NodeType;
+use decisionengine::variables::{format_variable_cycle, variable_references, VariableTypes};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// A problem found in a decision strategy, located by a JSON pointer
+/// (RFC 6901) into the strategy document.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ValidationError {
+    pub pointer: String,
+    pub message: String,
+}
+
+struct Validator {
+    errors: Vec<ValidationError>,
+    /// Number of enclosing array operations, in which `element` nodes may be
+    /// used.
+    element_depth: usize,
+    /// Names defined in the strategy's variables section.
+    variables: HashSet<String>,
+    /// Types inferred for the variables whose definitions are valid.
+    variable_types: VariableTypes,
+}
+
+fn child_pointer(pointer: &str, key: &str) -> String {
+    format!("{}/{}", pointer, key.replace("~", "~0").replace("/", "~1"))
+}
+
+fn index_pointer(pointer: &str, index: usize) -> String {
+    format!("{}/{}", pointer, index)
+}
+
+fn parse_condition_id(value: &Value) -> Option<i32> {
+    value.as_str().and_then(|id| id.parse::<i32>().ok())
+}
+
+/// Checks a decision strategy without deserializing it, collecting every
+/// problem that would otherwise surface as a panic at decision time.
+pub fn validate_strategy(value: &Value) -> Vec<ValidationError> {
+    let mut validator = Validator {
+        errors: Vec::new(),
+        element_depth: 0,
+        variables: HashSet::new(),
+        variable_types: VariableTypes::new(),
+    };
+    if value["type"].as_str() != Some("module") {
+        validator.error("/type", "Decision strategy must be a module.");
+    }
+    validator.validate_variables(&value["variables"], "/variables");
+    validator.validate_module(value, "");
+    validator.errors
+}
+
+impl Validator {
+    fn error(&mut self, pointer: &str, message: &str) {
+        self.errors.push(ValidationError {
+            pointer: if pointer.is_empty() {
+                String::from("/")
+            } else {
+                pointer.to_string()
+            },
+            message: message.to_string(),
+        });
+    }
+
+    fn validate_variables(&mut self, value: &Value, pointer: &str) {
+        let definitions = match value {
+            Value::Null => return,
+            Value::Object(definitions) => definitions,
+            _ => return self.error(pointer, "Variables must be an object of named expressions."),
+        };
+        self.variables = definitions.keys().cloned().collect();
+
+        let references: HashMap<String, Vec<String>> = definitions
+            .iter()
+            .map(|(name, definition)| (name.clone(), variable_references(definition)))
+            .collect();
+        if let Some(cycle) = find_cycle(&references) {
+            self.error(pointer, &format_variable_cycle(&cycle));
+        }
+
+        // Types each variable once the variables it references are typed, so
+        // that references to it are checked against its definition.
+        let mut pending: Vec<&String> = definitions.keys().collect();
+        while !pending.is_empty() {
+            let (ready, waiting): (Vec<&String>, Vec<&String>) = pending.iter().partition(|name| {
+                references[**name]
+                    .iter()
+                    .all(|reference| !pending.contains(&reference))
+            });
+            // Variables in a cycle, which has been reported, are checked
+            // without their types.
+            let ready = if ready.is_empty() {
+                waiting.clone()
+            } else {
+                ready
+            };
+            for name in &ready {
+                let pointer = child_pointer(pointer, name);
+                if let Some(node_type) =
+                    self.validate_typed_node(&definitions[*name], &pointer, NodeType::Any)
+                {
+                    self.variable_types.insert((*name).clone(), node_type);
+                }
+            }
+            pending.retain(|name| !ready.contains(name));
+        }
+    }
+
+    fn expect_str<'a>(&mut self, value: &'a Value, key: &str, pointer: &str) -> Option<&'a str> {
+        let s = value[key].as_str();
+        if s.is_none() {
+            self.error(
+                &child_pointer(pointer, key),
+                &format!("Expected string field \"{}\".", key),
+            );
+        }
+        s
+    }
+
+    fn expect_array<'a>(
+        &mut self,
+        value: &'a Value,
+        key: &str,
+        pointer: &str,
+    ) -> Option<&'a Vec<Value>> {
+        let a = value[key].as_array();
+        if a.is_none() {
+            self.error(
+                &child_pointer(pointer, key),
+                &format!("Expected array field \"{}\".", key),
+            );
+        }
+        a
+    }
+
+    fn expect_integer(&mut self, value: &Value, key: &str, pointer: &str) {
+        if !value[key].is_i64() {
+            self.error(
+                &child_pointer(pointer, key),
+                &format!("Expected integer field \"{}\".", key),
+            );
+        }
+    }
+
+    fn expect_i32(&mut self, value: &Value, key: &str, pointer: &str) {
+        let in_range = value[key].as_i64().map_or(false, |n| {
+            n >= i64::from(i32::min_value()) && n <= i64::from(i32::max_value())
+        });
+        if !in_range {
+            self.error(
+                &child_pointer(pointer, key),
+                &format!("Expected 32-bit integer field \"{}\".", key),
+            );
+        }
+    }
+
+    fn expect_optional_str(&mut self, value: &Value, keys: &[&str], pointer: &str) {
+        for key in keys {
+            if !value[*key].is_null() && !value[*key].is_string() {
+                self.error(
+                    &child_pointer(pointer, key),
+                    &format!("Expected string field \"{}\".", key),
+                );
+            }
+        }
+    }
+
+    fn expect_number(&mut self, value: &Value, key: &str, pointer: &str) {
+        let is_decimal = match value[key] {
+            Value::Number(ref n) => parse_decimal(n).is_some(),
+            _ => false,
+        };
+        if !is_decimal {
+            self.error(
+                &child_pointer(pointer, key),
+                &format!("Expected numeric field \"{}\".", key),
+            );
+        }
+    }
+
+    fn validate_eval_result(&mut self, value: &Value, pointer: &str) {
+        match value.as_str() {
+            Some(result) if parse_eval_result(result).is_some() => {}
+            _ => self.error(
+                pointer,
+                &format!(
+                    "Unknown outcome {}, expected ACCEPT, REFER or REJECT.",
+                    value
+                ),
+            ),
+        }
+    }
+
+    fn validate_module(&mut self, value: &Value, pointer: &str) {
+        if !value.is_object() {
+            return self.error(pointer, "Expected a module object.");
+        }
+        self.expect_str(value, "module_name", pointer);
+
+        let module_type = match self.expect_str(value, "module_type", pointer) {
+            Some(module_type) => module_type,
+            None => return,
+        };
+
+        match module_type {
+            "all" | "any" | "threshold" | "first-match" => {}
+            "scorecard" => return self.validate_scorecard(value, pointer),
+            _ => {
+                return self.error(
+                    &child_pointer(pointer, "module_type"),
+                    &format!("Unknown module_type: {}", module_type),
+                )
+            }
+        }
+
+        if module_type == "threshold" {
+            match (value["threshold"].as_u64(), value["children"].as_array()) {
+                (None, _) => self.error(
+                    &child_pointer(pointer, "threshold"),
+                    "Expected non-negative integer field \"threshold\".",
+                ),
+                (Some(threshold), Some(children)) if threshold as usize > children.len() => self
+                    .error(
+                        &child_pointer(pointer, "threshold"),
+                        &format!(
+                            "Threshold {} can never be reached with {} children.",
+                            threshold,
+                            children.len()
+                        ),
+                    ),
+                _ => {}
+            }
+        }
+        if module_type == "first-match" {
+            self.validate_eval_result(&value["default"], &child_pointer(pointer, "default"));
+        }
+        if !value["precedence"].is_null() {
+            if module_type == "first-match" {
+                self.error(
+                    &child_pointer(pointer, "precedence"),
+                    "First-match modules return their first decided child and take no precedence.",
+                );
+            } else {
+                self.validate_precedence(
+                    &value["precedence"],
+                    &child_pointer(pointer, "precedence"),
+                );
+            }
+        }
+
+        if let Some(children) = self.expect_array(value, "children", pointer) {
+            let children_pointer = child_pointer(pointer, "children");
+            for (i, child) in children.iter().enumerate() {
+                let item_pointer = index_pointer(&children_pointer, i);
+                match child["type"].as_str() {
+                    Some("rule") => self.validate_rule(child, &item_pointer),
+                    Some("module") => self.validate_module(child, &item_pointer),
+                    _ => self.error(
+                        &item_pointer,
+                        "Module children must be of type \"rule\" or \"module\".",
+                    ),
+                }
+            }
+        }
+    }
+
+    fn validate_precedence(&mut self, value: &Value, pointer: &str) {
+        let order = match value.as_array() {
+            Some(order) => order,
+            None => return self.error(pointer, "Expected an array of outcomes."),
+        };
+        for (i, result) in order.iter().enumerate() {
+            self.validate_eval_result(result, &index_pointer(pointer, i));
+        }
+        for result in &["ACCEPT", "REFER", "REJECT"] {
+            if order.iter().filter(|r| r.as_str() == Some(result)).count() != 1 {
+                self.error(
+                    pointer,
+                    &format!("Precedence must list {} exactly once.", result),
+                );
+            }
+        }
+    }
+
+    fn validate_scorecard(&mut self, value: &Value, pointer: &str) {
+        if let Some(characteristics) = self.expect_array(value, "characteristics", pointer) {
+            let characteristics_pointer = child_pointer(pointer, "characteristics");
+            for (i, characteristic) in characteristics.iter().enumerate() {
+                let characteristic_pointer = index_pointer(&characteristics_pointer, i);
+                self.expect_str(
+                    characteristic,
+                    "characteristic_name",
+                    &characteristic_pointer,
+                );
+                self.validate_typed_node(
+                    &characteristic["value"],
+                    &child_pointer(&characteristic_pointer, "value"),
+                    NodeType::Numeric,
+                );
+                if !characteristic["default_points"].is_null() {
+                    self.expect_i32(characteristic, "default_points", &characteristic_pointer);
+                }
+                if let Some(bins) =
+                    self.expect_array(characteristic, "bins", &characteristic_pointer)
+                {
+                    let bins_pointer = child_pointer(&characteristic_pointer, "bins");
+                    for (j, bin) in bins.iter().enumerate() {
+                        let bin_pointer = index_pointer(&bins_pointer, j);
+                        for key in &["min", "max"] {
+                            if !bin[*key].is_null() {
+                                self.expect_number(bin, key, &bin_pointer);
+                            }
+                        }
+                        self.expect_i32(bin, "points", &bin_pointer);
+                    }
+                }
+            }
+        }
+        if let Some(cutoffs) = self.expect_array(value, "cutoffs", pointer) {
+            let cutoffs_pointer = child_pointer(pointer, "cutoffs");
+            for (i, cutoff) in cutoffs.iter().enumerate() {
+                let cutoff_pointer = index_pointer(&cutoffs_pointer, i);
+                self.expect_i32(cutoff, "min_score", &cutoff_pointer);
+                self.validate_eval_result(
+                    &cutoff["result"],
+                    &child_pointer(&cutoff_pointer, "result"),
+                );
+                self.expect_optional_str(cutoff, &["reason_code", "message"], &cutoff_pointer);
+            }
+        }
+        self.validate_eval_result(&value["default"], &child_pointer(pointer, "default"));
+        self.expect_optional_str(value, &["default_reason_code", "default_message"], pointer);
+    }
+
+    fn validate_rule(&mut self, value: &Value, pointer: &str) {
+        self.expect_integer(value, "rule_id", pointer);
+        self.expect_str(value, "rule_name", pointer);
+
+        let conditions = match self.expect_array(value, "conditions", pointer) {
+            Some(conditions) => conditions,
+            None => return,
+        };
+        let conditions_pointer = child_pointer(pointer, "conditions");
+
+        let mut condition_ids = HashSet::new();
+        for (i, condition) in conditions.iter().enumerate() {
+            let condition_pointer = index_pointer(&conditions_pointer, i);
+            match parse_condition_id(&condition["condition_id"]) {
+                Some(id) => {
+                    if !condition_ids.insert(id) {
+                        self.error(
+                            &child_pointer(&condition_pointer, "condition_id"),
+                            &format!("Duplicate condition_id {}.", id),
+                        );
+                    }
+                }
+                None => self.error(
+                    &child_pointer(&condition_pointer, "condition_id"),
+                    "Expected condition_id to be an integer string.",
+                ),
+            }
+        }
+        if !condition_ids.contains(&1) {
+            self.error(
+                &conditions_pointer,
+                "Rule has no condition with condition_id \"1\".",
+            );
+        }
+
+        let mut gotos: HashMap<i32, Vec<i32>> = HashMap::new();
+        for condition in conditions {
+            if let Some(id) = parse_condition_id(&condition["condition_id"]) {
+                let targets = gotos.entry(id).or_insert_with(Vec::new);
+                for branch in &["true", "false", "on_missing"] {
+                    let decision = &condition[*branch];
+                    if decision["type"].as_str() == Some("goto") {
+                        if let Some(target) = parse_condition_id(&decision["value"]) {
+                            targets.push(target);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(cycle) = find_cycle(&gotos) {
+            self.error(&conditions_pointer, &format_goto_cycle(&cycle));
+        }
+
+        for (i, condition) in conditions.iter().enumerate() {
+            let condition_pointer = index_pointer(&conditions_pointer, i);
+            self.validate_typed_node(
+                &condition["condition"],
+                &child_pointer(&condition_pointer, "condition"),
+                NodeType::Boolean,
+            );
+            if !condition["on_missing"].is_null() {
+                self.validate_condition_decision(
+                    &condition["on_missing"],
+                    &child_pointer(&condition_pointer, "on_missing"),
+                    &condition_ids,
+                );
+            }
+            for branch in &["true", "false"] {
+                self.validate_condition_decision(
+                    &condition[*branch],
+                    &child_pointer(&condition_pointer, branch),
+                    &condition_ids,
+                );
+            }
+        }
+    }
+
+    fn validate_condition_decision(
+        &mut self,
+        value: &Value,
+        pointer: &str,
+        condition_ids: &HashSet<i32>,
+    ) {
+        match value["type"].as_str() {
+            Some("return") => {
+                self.validate_eval_result(&value["value"], &child_pointer(pointer, "value"));
+                self.expect_optional_str(value, &["reason_code", "message"], pointer);
+            }
+            Some("goto") => match parse_condition_id(&value["value"]) {
+                Some(id) => {
+                    if !condition_ids.contains(&id) {
+                        self.error(
+                            &child_pointer(pointer, "value"),
+                            &format!("goto target {} does not exist.", id),
+                        );
+                    }
+                }
+                None => self.error(
+                    &child_pointer(pointer, "value"),
+                    "Expected goto target to be an integer string.",
+                ),
+            },
+            _ => self.error(
+                &child_pointer(pointer, "type"),
+                "Condition decision must be of type \"return\" or \"goto\".",
+            ),
+        }
+    }
+
+    /// Validates the node and, if it is well formed, infers its type and
+    /// checks it against `expected`, returning the type if it matches.
+    fn validate_typed_node(
+        &mut self,
+        value: &Value,
+        pointer: &str,
+        expected: NodeType,
+    ) -> Option<NodeType> {
+        let errors = self.errors.len();
+        self.validate_node(value, pointer);
+        if self.errors.len() != errors {
+            return None;
+        }
+
+        let node = match deserialize_node(value) {
+            Ok((node, _)) => node,
+            Err(DecisionEngineError::MalformedStrategy(msg)) => {
+                self.error(pointer, &msg);
+                return None;
+            }
+            Err(error) => {
+                self.error(pointer, &error.to_string());
+                return None;
+            }
+        };
+        match node.node_type(&self.variable_types) {
+            Ok(node_type) => {
+                if node_type.is_compatible(&expected) {
+                    return Some(node_type);
+                }
+                self.error(
+                    pointer,
+                    &format!("Expected {} expression, got {}.", expected, node_type),
+                );
+            }
+            Err(msg) => self.error(pointer, &msg),
+        }
+        None
+    }
+
+    fn validate_node(&mut self, value: &Value, pointer: &str) {
+        if let Some(source) = value.as_str() {
+            return self.validate_expression(source, pointer);
+        }
+        match value["type"].as_str() {
+            Some("constant") if value.get("value").is_none() => self.error(
+                &child_pointer(pointer, "value"),
+                "Can't deserialize constant input: null",
+            ),
+            Some("constant") => match value["value_type"] {
+                Value::Null => {
+                    self.validate_constant(&value["value"], &child_pointer(pointer, "value"))
+                }
+                Value::String(ref value_type)
+                    if value_type == "date" || value_type == "datetime" =>
+                {
+                    self.validate_temporal_constant(
+                        &value["value"],
+                        &child_pointer(pointer, "value"),
+                        value_type,
+                    )
+                }
+                _ => self.error(
+                    &child_pointer(pointer, "value_type"),
+                    "Constant value_type must be \"date\" or \"datetime\".",
+                ),
+            },
+            Some("now") => {}
+            Some("input") => match value["value"].as_str() {
+                Some(path) => {
+                    if parse_input_node(path).is_none() {
+                        self.error(
+                            &child_pointer(pointer, "value"),
+                            &format!("Unknown input {}.", path),
+                        );
+                    }
+                }
+                None => self.error(
+                    &child_pointer(pointer, "value"),
+                    "Expected input path string.",
+                ),
+            },
+            Some("variable") => match value["value"].as_str() {
+                Some(name) => {
+                    if !self.variables.contains(name) {
+                        self.error(
+                            &child_pointer(pointer, "value"),
+                            &format!("Unknown variable {}.", name),
+                        );
+                    }
+                }
+                None => self.error(
+                    &child_pointer(pointer, "value"),
+                    "Expected variable name string.",
+                ),
+            },
+            Some("element") => {
+                if self.element_depth == 0 {
+                    self.error(
+                        pointer,
+                        "Element can only be used in the rvalue of an array operation.",
+                    );
+                }
+                if !value["value"].is_null() && !value["value"].is_string() {
+                    self.error(
+                        &child_pointer(pointer, "value"),
+                        "Expected element field name string.",
+                    );
+                }
+            }
+            Some("op") => match value["op"].as_str() {
+                Some(op) => self.validate_op_node(value, pointer, op),
+                None => self.error(&child_pointer(pointer, "op"), "Expected operation string."),
+            },
+            _ => self.error(
+                &child_pointer(pointer, "type"),
+                "Node must be of type \"constant\", \"input\", \"op\", \"variable\", \"element\" or \"now\".",
+            ),
+        }
+    }
+
+    /// Validates an expression string. Problems within it are reported at
+    /// the string itself, as there is no JSON to point into.
+    fn validate_expression(&mut self, source: &str, pointer: &str) {
+        match parse_expression(source) {
+            Ok(parsed) => {
+                let errors = self.errors.len();
+                self.validate_node(&parsed, pointer);
+                for error in &mut self.errors[errors..] {
+                    error.pointer = pointer.to_string();
+                }
+            }
+            Err(msg) => self.error(pointer, &msg),
+        }
+    }
+
+    fn validate_op_node(&mut self, value: &Value, pointer: &str, op: &str) {
+        match operation_arity(value) {
+            Arity::Binary if binary_operation(op).is_some() => {
+                self.validate_node(&value["lvalue"], &child_pointer(pointer, "lvalue"));
+                let binds_element = binary_operation(op).map_or(false, |op| op.binds_element());
+                if binds_element {
+                    self.element_depth += 1;
+                }
+                self.validate_node(&value["rvalue"], &child_pointer(pointer, "rvalue"));
+                if binds_element {
+                    self.element_depth -= 1;
+                }
+            }
+            Arity::Unary if unary_operation(op).is_some() => {
+                self.validate_node(&value["value"], &child_pointer(pointer, "value"));
+            }
+            Arity::Ternary if ternary_operation(op).is_some() => match value["args"].as_array() {
+                Some(args) if args.len() == 3 => {
+                    let args_pointer = child_pointer(pointer, "args");
+                    for (i, arg) in args.iter().enumerate() {
+                        self.validate_node(arg, &index_pointer(&args_pointer, i));
+                    }
+                }
+                _ => self.error(
+                    &child_pointer(pointer, "args"),
+                    &format!("Operation {} expects an array of 3 args.", op),
+                ),
+            },
+            _ if binary_operation(op).is_some()
+                || unary_operation(op).is_some()
+                || ternary_operation(op).is_some() =>
+            {
+                self.error(pointer, &operation_error(value))
+            }
+            _ => self.error(
+                &child_pointer(pointer, "op"),
+                &format!("Unknown operation {}.", op),
+            ),
+        }
+    }
+
+    fn validate_temporal_constant(&mut self, value: &Value, pointer: &str, value_type: &str) {
+        match value {
+            Value::Array(values) => {
+                for (i, v) in values.iter().enumerate() {
+                    self.validate_temporal_constant(v, &index_pointer(pointer, i), value_type);
+                }
+            }
+            Value::Null => {}
+            Value::String(ref s) if value_type == "date" && parse_date(s).is_some() => {}
+            Value::String(ref s) if value_type == "datetime" && parse_datetime(s).is_some() => {}
+            _ => self.error(
+                pointer,
+                &format!("Expected ISO 8601 {}, got {}.", value_type, value),
+            ),
+        }
+    }
+
+    fn validate_constant(&mut self, value: &Value, pointer: &str) {
+        match value {
+            Value::Array(values) => {
+                for (i, v) in values.iter().enumerate() {
+                    self.validate_constant(v, &index_pointer(pointer, i));
+                }
+            }
+            Value::Null | Value::Bool(_) | Value::String(_) => {}
+            Value::Number(ref n) if parse_decimal(n).is_some() => {}
+            _ => self.error(
+                pointer,
+                &format!("Can't deserialize constant input: {}", value),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decisionengine::testing::rule_strategy;
+
+    fn with_variables(mut strategy: Value, variables: Value) -> Value {
+        strategy["variables"] = variables;
+        strategy
+    }
+
+    fn messages(strategy: &Value) -> Vec<(String, String)> {
+        validate_strategy(strategy)
+            .into_iter()
+            .map(|error| (error.pointer, error.message))
+            .collect()
+    }
+
+    fn error(pointer: &str, message: &str) -> (String, String) {
+        (pointer.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn strategy_must_be_a_module() {
+        assert_eq!(
+            messages(&json!([])),
+            vec![
+                error("/type", "Decision strategy must be a module."),
+                error("/", "Expected a module object."),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_its_pointer() {
+        let mut strategy = rule_strategy(json!({
+            "type": "input",
+            "value": "application_data_v1.shoe_size",
+        }));
+        strategy["children"][0]["rule_name"] = Value::Null;
+        strategy["children"][0]["conditions"][0]["false"] = json!({"type": "goto", "value": "2"});
+        strategy["children"][0]["conditions"][0]["true"]["value"] = json!("MAYBE");
+        strategy["children"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({"type": "condition"}));
+        assert_eq!(
+            messages(&strategy),
+            vec![
+                error(
+                    "/children/0/rule_name",
+                    "Expected string field \"rule_name\".",
+                ),
+                error(
+                    "/children/0/conditions/0/condition/value",
+                    "Unknown input application_data_v1.shoe_size.",
+                ),
+                error(
+                    "/children/0/conditions/0/true/value",
+                    "Unknown outcome \"MAYBE\", expected ACCEPT, REFER or REJECT.",
+                ),
+                error(
+                    "/children/0/conditions/0/false/value",
+                    "goto target 2 does not exist.",
+                ),
+                error(
+                    "/children/1",
+                    "Module children must be of type \"rule\" or \"module\".",
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rules_need_unique_condition_ids_starting_at_one() {
+        let mut strategy = rule_strategy(Value::from("true"));
+        strategy["children"][0]["conditions"][0]["condition_id"] = json!("2");
+        let condition = strategy["children"][0]["conditions"][0].clone();
+        strategy["children"][0]["conditions"]
+            .as_array_mut()
+            .unwrap()
+            .push(condition);
+        assert_eq!(
+            messages(&strategy),
+            vec![
+                error(
+                    "/children/0/conditions/1/condition_id",
+                    "Duplicate condition_id 2.",
+                ),
+                error(
+                    "/children/0/conditions",
+                    "Rule has no condition with condition_id \"1\".",
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn pointers_escape_keys() {
+        let strategy = with_variables(
+            rule_strategy(Value::from("true")),
+            json!({"a/b~c": {"type": "op", "op": "frobnicate", "value": 1}}),
+        );
+        assert_eq!(
+            messages(&strategy),
+            vec![error(
+                "/variables/a~1b~0c/op",
+                "Unknown operation frobnicate.",
+            )]
+        );
+    }
+
+    #[test]
+    fn expression_errors_are_reported_at_the_expression() {
+        let strategy = rule_strategy(Value::from("application_data_v1.shoe_size > 1"));
+        assert_eq!(
+            messages(&strategy),
+            vec![error(
+                "/children/0/conditions/0/condition",
+                "Unknown input application_data_v1.shoe_size.",
+            )]
+        );
+    }
+
+    #[test]
+    fn conditions_must_be_boolean() {
+        assert_eq!(
+            messages(&rule_strategy(Value::from("application_data_v1.age + 1"))),
+            vec![error(
+                "/children/0/conditions/0/condition",
+                "Expected Boolean expression, got Numeric.",
+            )]
+        );
+        assert_eq!(
+            messages(&rule_strategy(Value::from(
+                "application_data_v1.age && true"
+            ))),
+            vec![error(
+                "/children/0/conditions/0/condition",
+                "Operation && expects (Boolean, Boolean), got (Numeric, Boolean).",
+            )]
+        );
+    }
+
+    #[test]
+    fn first_match_takes_no_precedence() {
+        let mut strategy = rule_strategy(Value::from("true"));
+        strategy["module_type"] = json!("first-match");
+        strategy["default"] = json!("REFER");
+        assert!(messages(&strategy).is_empty());
+
+        strategy["precedence"] = json!(["ACCEPT", "REFER", "REJECT"]);
+        assert_eq!(
+            messages(&strategy),
+            vec![(
+                String::from("/precedence"),
+                String::from(
+                    "First-match modules return their first decided child and take no precedence."
+                ),
+            )]
+        );
+
+        strategy["module_type"] = json!("any");
+        assert!(messages(&strategy).is_empty());
+    }
+
+    #[test]
+    fn thresholds_must_be_reachable() {
+        let mut strategy = rule_strategy(Value::from("true"));
+        strategy["module_type"] = json!("threshold");
+        strategy["threshold"] = json!(1);
+        assert!(messages(&strategy).is_empty());
+
+        strategy["threshold"] = json!(2);
+        assert_eq!(
+            messages(&strategy),
+            vec![error(
+                "/threshold",
+                "Threshold 2 can never be reached with 1 children.",
+            )]
+        );
+    }
+
+    #[test]
+    fn numeric_variable_is_not_a_condition() {
+        let strategy = with_variables(
+            rule_strategy(json!({"type": "variable", "value": "age"})),
+            json!({"age": "application_data_v1.age"}),
+        );
+        assert_eq!(
+            messages(&strategy),
+            vec![(
+                String::from("/children/0/conditions/0/condition"),
+                String::from("Expected Boolean expression, got Numeric."),
+            )]
+        );
+    }
+
+    #[test]
+    fn variable_operands_are_type_checked() {
+        let strategy = with_variables(
+            rule_strategy(Value::from("regex_contains(age, \"^1\")")),
+            json!({"age": "application_data_v1.age"}),
+        );
+        let errors = messages(&strategy);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "/children/0/conditions/0/condition");
+        assert!(errors[0].1.contains("regex_contains"), "{}", errors[0].1);
+    }
+
+    #[test]
+    fn variable_types_follow_references() {
+        let strategy = with_variables(
+            rule_strategy(Value::from("next_age")),
+            json!({
+                "next_age": "age + 1",
+                "age": "application_data_v1.age",
+            }),
+        );
+        assert_eq!(
+            messages(&strategy),
+            vec![(
+                String::from("/children/0/conditions/0/condition"),
+                String::from("Expected Boolean expression, got Numeric."),
+            )]
+        );
+
+        let strategy = with_variables(
+            rule_strategy(Value::from("adult && named")),
+            json!({
+                "adult": "age >= 18",
+                "age": "application_data_v1.age",
+                "named": "length(application_data_v1.first_name) > 0",
+            }),
+        );
+        assert!(messages(&strategy).is_empty());
+    }
+
+    #[test]
+    fn invalid_variables_do_not_cascade() {
+        let strategy = with_variables(
+            rule_strategy(Value::from("broken")),
+            json!({"broken": "application_data_v1.age && true"}),
+        );
+        let errors = messages(&strategy);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].0, "/variables/broken");
+    }
+
+    #[test]
+    fn variable_cycles_are_reported() {
+        let strategy = with_variables(rule_strategy(Value::from("a")), json!({"a": "b", "b": "a"}));
+        let errors = messages(&strategy);
+        assert_eq!(errors[0].0, "/variables");
+        assert!(errors[0].1.contains("Cycle"), "{}", errors[0].1);
+    }
+}
@@ -0,0 +1,212 @@
+extern crate serde_json;
+
+use decisionengine::datasource::DecisionDataset;
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::expressions::parse_expression;
+use decisionengine::nodes::{deserialize_node, EvalNode, NodeResult};
+use decisionengine::rules::find_cycle;
+use decisionengine::types::NodeType;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Named expressions from the `"variables"` section of a strategy. Each is
+/// evaluated at most once per decision, when it is first referenced.
+pub struct Variables {
+    nodes: HashMap<String, Box<EvalNode>>,
+}
+
+impl Variables {
+    pub fn empty() -> Self {
+        Variables {
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn eval(&self, name: &str, input: &mut DecisionDataset) -> NodeResult {
+        match self.nodes.get(name) {
+            Some(node) => node.eval(input),
+            None => NodeResult::Err(format!("Unknown variable {}.", name)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The `"variables"` section the variables were loaded from, in
+    /// canonical form.
+    pub fn serialize(&self) -> Value {
+        Value::Object(
+            self.nodes
+                .iter()
+                .map(|(name, node)| (name.clone(), node.serialize()))
+                .collect(),
+        )
+    }
+}
+
+/// Types of the strategy's variables, inferred from their definitions.
+pub type VariableTypes = HashMap<String, NodeType>;
+
+/// Reference to a strategy variable.
+struct VariableNode {
+    name: String,
+}
+
+impl EvalNode for VariableNode {
+    fn eval(&self, input: &mut DecisionDataset) -> NodeResult {
+        input.variable(&self.name)
+    }
+
+    /// Variables whose type could not be inferred, for example because
+    /// their definition is invalid, may hold any type.
+    fn node_type(&self, variables: &VariableTypes) -> Result<NodeType, String> {
+        Ok(variables.get(&self.name).cloned().unwrap_or(NodeType::Any))
+    }
+
+    fn serialize(&self) -> Value {
+        let mut node = Map::new();
+        node.insert(String::from("type"), Value::from("variable"));
+        node.insert(String::from("value"), Value::from(self.name.as_str()));
+        Value::Object(node)
+    }
+}
+
+pub fn deserialize_variable_node(name: &str) -> Result<(Box<EvalNode>, bool), DecisionEngineError> {
+    Ok((
+        Box::new(VariableNode {
+            name: name.to_string(),
+        }),
+        false,
+    ))
+}
+
+/// Names of the variables referenced anywhere in a node.
+pub fn variable_references(value: &Value) -> Vec<String> {
+    if let Some(source) = value.as_str() {
+        return parse_expression(source)
+            .map(|parsed| variable_references(&parsed))
+            .unwrap_or_default();
+    }
+    let mut references = Vec::new();
+    match value["type"].as_str() {
+        Some("variable") => references.extend(value["value"].as_str().map(String::from)),
+        Some("op") => {
+            for key in &["lvalue", "rvalue", "value"] {
+                references.extend(variable_references(&value[*key]));
+            }
+            for arg in value["args"].as_array().map_or(&[][..], |args| &args[..]) {
+                references.extend(variable_references(arg));
+            }
+        }
+        _ => {}
+    }
+    references
+}
+
+pub fn format_variable_cycle(cycle: &[String]) -> String {
+    format!("Cycle between variables {}.", cycle.join(" -> "))
+}
+
+pub fn deserialize_variables(value: &Value) -> Result<Variables, DecisionEngineError> {
+    let definitions = match value {
+        Value::Null => return Ok(Variables::empty()),
+        Value::Object(definitions) => definitions,
+        _ => {
+            return Err(DecisionEngineError::MalformedStrategy(String::from(
+                "Variables must be an object of named expressions.",
+            )))
+        }
+    };
+
+    let references: HashMap<String, Vec<String>> = definitions
+        .iter()
+        .map(|(name, definition)| (name.clone(), variable_references(definition)))
+        .collect();
+    if let Some(cycle) = find_cycle(&references) {
+        return Err(DecisionEngineError::MalformedStrategy(
+            format_variable_cycle(&cycle),
+        ));
+    }
+
+    let mut nodes = HashMap::new();
+    for (name, definition) in definitions {
+        let (node, _) = deserialize_node(definition)?;
+        nodes.insert(name.clone(), node);
+    }
+    Ok(Variables { nodes: nodes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decisionengine::testing::{detailed, rule, rule_strategy};
+    use decisionengine::{deserialize_strategy, EvalMode};
+
+    fn strategy() -> Value {
+        let mut strategy = rule_strategy(Value::from("adult"));
+        strategy["children"]
+            .as_array_mut()
+            .unwrap()
+            .push(rule(2, Value::from("adult && monthly_income > 1000")));
+        strategy["variables"] = json!({
+            "adult": "application_data_v1.age >= 18",
+            "monthly_income": "application_data_v1.income / 12",
+            "unused": "application_data_v1.income > 0",
+        });
+        strategy
+    }
+
+    #[test]
+    fn variables_are_shared_between_rules() {
+        let details =
+            detailed(&strategy(), json!({"income": 24000}), EvalMode::Exhaustive).unwrap();
+        assert_eq!(details["result"], "Accept");
+        assert_eq!(
+            details["variables"],
+            json!({"adult": true, "monthly_income": 2000})
+        );
+
+        let details = detailed(&strategy(), json!({"income": 6000}), EvalMode::Exhaustive).unwrap();
+        assert_eq!(details["result"], "Reject");
+    }
+
+    #[test]
+    fn references_are_found_in_expressions_and_nodes() {
+        assert_eq!(
+            variable_references(&Value::from("adult && coalesce(score, 0) > limit")),
+            vec!["adult", "score", "limit"]
+        );
+        assert_eq!(
+            variable_references(&json!({
+                "type": "op",
+                "op": "substring",
+                "args": [{"type": "variable", "value": "name"}, 0, "length"],
+            })),
+            vec!["name", "length"]
+        );
+    }
+
+    #[test]
+    fn cycles_between_variables_are_rejected() {
+        match deserialize_variables(&json!({"a": "b + 1", "b": "c", "c": "a"})) {
+            Err(DecisionEngineError::MalformedStrategy(msg)) => {
+                assert_eq!(msg, "Cycle between variables a -> b -> c -> a.")
+            }
+            _ => panic!("expected a cycle"),
+        }
+    }
+
+    #[test]
+    fn variables_are_serialized_with_the_strategy() {
+        let serialized = deserialize_strategy(&strategy()).unwrap().serialize();
+        assert_eq!(
+            serialized["variables"]["adult"]["lvalue"],
+            json!({"type": "input", "value": "application_data_v1.age"})
+        );
+        assert_eq!(
+            deserialize_strategy(&serialized).unwrap().serialize(),
+            serialized
+        );
+    }
+}
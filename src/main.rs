@@ -1,4 +1,5 @@
 extern crate bodyparser;
+extern crate chrono;
 extern crate clap;
 #[macro_use]
 extern crate diesel;
@@ -8,11 +9,15 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate router;
+extern crate rust_decimal;
+#[macro_use]
 extern crate serde_json;
+extern crate sha2;
+extern crate unicode_normalization;
 
 use clap::{App, Arg};
 use diesel::pg::PgConnection;
-use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use dotenv::dotenv;
 use iron::mime::Mime;
 use iron::prelude::*;
@@ -21,177 +26,602 @@ use router::Router;
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
+use std::sync::Arc;
 
 mod decisionengine;
-use decisionengine::Evaluatable;
+use decisionengine::cache::StrategyCache;
+use decisionengine::errors::DecisionEngineError;
+use decisionengine::{EvalMode, Evaluatable};
 
 #[derive(Serialize, Deserialize, Clone)]
 struct DecisionRequest {
     application_data: decisionengine::datasource::applicationdata::ApplicationDataV1,
     decision_strategy_id: i32,
     detailed: Option<bool>,
+    /// With `detailed`, stops evaluating each module once its result is
+    /// decided rather than evaluating the whole tree.
+    short_circuit: Option<bool>,
 }
 
-pub fn establish_connection() -> PgConnection {
+type ConnectionPool = Pool<ConnectionManager<PgConnection>>;
+
+pub fn connection_pool() -> Result<ConnectionPool, DecisionEngineError> {
     dotenv().ok();
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    PgConnection::establish(&database_url).expect(&format!("Error connecting to {}", database_url))
+    let database_url = env::var("DATABASE_URL")
+        .map_err(|_| DecisionEngineError::Database(String::from("DATABASE_URL must be set")))?;
+    Ok(Pool::builder().build(ConnectionManager::new(database_url))?)
 }
 
-fn create_decision_strategy(req: &mut Request) -> IronResult<Response> {
-    let connection = establish_connection();
+/// State shared between the threads handling requests.
+struct ServerState {
+    pool: ConnectionPool,
+    strategies: StrategyCache,
+    step_budget: usize,
+}
 
-    let body = req.get::<bodyparser::Raw>();
-    let content_type = "application/json".parse::<Mime>().unwrap();
+impl ServerState {
+    fn connection(
+        &self,
+    ) -> Result<PooledConnection<ConnectionManager<PgConnection>>, DecisionEngineError> {
+        Ok(self.pool.get()?)
+    }
+}
 
-    match body {
-        Ok(Some(json)) => {
-            let decision_strategy_json = serde_json::from_str(&json);
-            let decision_strategy = decisionengine::DecisionStrategy::create(
-                decision_strategy_json.unwrap(),
-                &connection,
-            );
-            return Ok(Response::with((
-                content_type,
-                status::Ok,
-                format!(
-                    "{{\"decision_strategy_id\": {}}}",
-                    decision_strategy.decision_strategy_id()
-                ),
-            )));
+/// Reads the per-decision step budget from `DECISION_STEP_BUDGET`, falling
+/// back to the default. Read once at startup.
+fn step_budget() -> Result<usize, DecisionEngineError> {
+    dotenv().ok();
+    match env::var("DECISION_STEP_BUDGET") {
+        Ok(budget) => {
+            parse_step_budget("DECISION_STEP_BUDGET", &budget).map_err(DecisionEngineError::Config)
         }
-        _ => Ok(Response::with(status::BadRequest)),
+        Err(_) => Ok(decisionengine::datasource::DEFAULT_STEP_BUDGET),
+    }
+}
+
+/// Parses a step budget given by the setting `name`, with the error message
+/// naming the setting.
+fn parse_step_budget(name: &str, budget: &str) -> Result<usize, String> {
+    budget
+        .parse::<usize>()
+        .map_err(|_| format!("{} must be a non-negative integer, got {}.", name, budget))
+}
+
+fn eval_mode(short_circuit: bool) -> EvalMode {
+    if short_circuit {
+        EvalMode::ShortCircuit
+    } else {
+        EvalMode::Exhaustive
+    }
+}
+
+/// The result of a strategy evaluated in detail. Only the children of a
+/// module can fail without failing the whole decision, so a strategy always
+/// has one.
+fn detailed_result(
+    details: &decisionengine::results::SubmoduleResult,
+) -> Result<&decisionengine::EvalResult, DecisionEngineError> {
+    details
+        .result()
+        .ok_or_else(|| DecisionEngineError::Evaluation(String::from("The strategy has no result.")))
+}
+
+fn result_name(result: &decisionengine::EvalResult) -> &'static str {
+    match result {
+        decisionengine::EvalResult::Accept => "accept",
+        decisionengine::EvalResult::Refer => "refer",
+        decisionengine::EvalResult::Reject => "reject",
     }
 }
 
-fn decision(req: &mut Request) -> IronResult<Response> {
-    let connection = establish_connection();
+/// Outcome recorded for decisions whose evaluation failed.
+const ERROR_OUTCOME: &str = "error";
 
+/// The outcome, result and details stored for an evaluation. Failed
+/// evaluations are stored too, with the error in place of the details, so
+/// that every decision requested is audited.
+fn recorded_evaluation(
+    evaluation: &Result<(&'static str, serde_json::Value), DecisionEngineError>,
+    reasons: &serde_json::Value,
+) -> (&'static str, Option<serde_json::Value>, serde_json::Value) {
+    match *evaluation {
+        Ok((outcome, ref details)) => (
+            outcome,
+            Some(json!({ "result": outcome, "reasons": reasons })),
+            details.clone(),
+        ),
+        Err(ref error) => (ERROR_OUTCOME, None, json!({ "error": error.to_string() })),
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<decisionengine::validation::ValidationError>,
+}
+
+fn json_response(status: status::Status, body: String) -> Response {
     let content_type = "application/json".parse::<Mime>().unwrap();
-    let struct_body = req.get::<bodyparser::Struct<DecisionRequest>>();
-    match struct_body {
-        Ok(Some(request)) => {
-            let mut decision_module = decisionengine::DecisionStrategy::from_id(
-                request.decision_strategy_id,
-                &connection,
-            ).get_module();
-
-            let mut decision_dataset =
-                decisionengine::datasource::DecisionDataset::new(request.application_data);
-
-            let result = decision_module.eval(&mut decision_dataset);
-            if request.detailed.is_none() || !request.detailed.unwrap() {
-                return Ok(Response::with((
-                    content_type,
-                    status::Ok,
-                    match result {
-                        decisionengine::EvalResult::Accept => "{ \"result\": \"accept\" }",
-                        _ => "{ \"result\": \"reject\" }",
-                    },
-                )));
-            } else {
-                let detailed_result = decisionengine::results::SubmoduleResult::ModuleResult(
-                    decisionengine::results::ModuleResult {
-                        module_id: String::from("CBRF Silver"),
-                        result: result.clone(),
-                        submodule_results: Vec::new(),
-                    },
-                );
-
-                let mut visitor = decisionengine::visitor::ResultAggregatingVisitor {
-                    stack: decisionengine::visitor::ResultStack::new(detailed_result),
-                    input: decision_dataset,
-                };
-
-                decision_module.accept(&mut visitor);
-
-                return Ok(Response::with((
-                    content_type,
-                    status::Ok,
-                    format!(
-                        "{{ \"result\": \"{}\", \"details\": {} }}",
-                        match result {
-                            decisionengine::EvalResult::Accept => "accept",
-                            _ => "reject",
-                        },
-                        serde_json::to_string(visitor.stack.get_result()).unwrap()
-                    ),
-                )));
+    Response::with((content_type, status, body))
+}
+
+fn error_response(error: DecisionEngineError) -> Response {
+    let status = match error {
+        DecisionEngineError::BadRequest(_) => status::BadRequest,
+        DecisionEngineError::StrategyNotFound(_) | DecisionEngineError::DecisionNotFound(_) => {
+            status::NotFound
+        }
+        DecisionEngineError::StrategyRetired(_) => status::Gone,
+        DecisionEngineError::InvalidStrategy(_) | DecisionEngineError::MalformedStrategy(_) => {
+            status::UnprocessableEntity
+        }
+        DecisionEngineError::Evaluation(_)
+        | DecisionEngineError::StepBudgetExceeded(_)
+        | DecisionEngineError::Config(_)
+        | DecisionEngineError::Database(_)
+        | DecisionEngineError::Io(_) => status::InternalServerError,
+    };
+    let body = ErrorResponse {
+        error: error.to_string(),
+        errors: match error {
+            DecisionEngineError::InvalidStrategy(errors) => errors,
+            _ => Vec::new(),
+        },
+    };
+    json_response(status, serde_json::to_string(&body).unwrap())
+}
+
+fn create_decision_strategy(req: &mut Request, state: &ServerState) -> IronResult<Response> {
+    Ok(handle_create_decision_strategy(req, state).unwrap_or_else(error_response))
+}
+
+fn handle_create_decision_strategy(
+    req: &mut Request,
+    state: &ServerState,
+) -> Result<Response, DecisionEngineError> {
+    let json = match req.get::<bodyparser::Raw>() {
+        Ok(Some(json)) => json,
+        Ok(None) => {
+            return Err(DecisionEngineError::BadRequest(String::from(
+                "Request body is empty.",
+            )))
+        }
+        Err(error) => return Err(DecisionEngineError::BadRequest(error.to_string())),
+    };
+
+    let decision_strategy_json: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|error| DecisionEngineError::BadRequest(error.to_string()))?;
+
+    let errors = decisionengine::validation::validate_strategy(&decision_strategy_json);
+    if !errors.is_empty() {
+        return Err(DecisionEngineError::InvalidStrategy(errors));
+    }
+
+    let connection = state.connection()?;
+    let decision_strategy =
+        decisionengine::DecisionStrategy::create(decision_strategy_json, &connection)?;
+    Ok(json_response(
+        status::Ok,
+        format!(
+            "{{\"decision_strategy_id\": {}}}",
+            decision_strategy.decision_strategy_id()
+        ),
+    ))
+}
+
+fn get_decision_strategy(req: &mut Request, state: &ServerState) -> IronResult<Response> {
+    Ok(handle_get_decision_strategy(req, state).unwrap_or_else(error_response))
+}
+
+fn handle_get_decision_strategy(
+    req: &mut Request,
+    state: &ServerState,
+) -> Result<Response, DecisionEngineError> {
+    let id = path_id(req)?;
+    let connection = state.connection()?;
+    let decision_strategy = decisionengine::DecisionStrategy::from_id(id, &connection)?;
+    let mut body = serde_json::to_value(decision_strategy.metadata()).unwrap();
+    body["decision_strategy_json"] = decision_strategy.decision_strategy_json().clone();
+    Ok(json_response(
+        status::Ok,
+        serde_json::to_string(&body).unwrap(),
+    ))
+}
+
+fn list_decision_strategies(req: &mut Request, state: &ServerState) -> IronResult<Response> {
+    Ok(handle_list_decision_strategies(req, state).unwrap_or_else(error_response))
+}
+
+fn handle_list_decision_strategies(
+    _req: &mut Request,
+    state: &ServerState,
+) -> Result<Response, DecisionEngineError> {
+    let connection = state.connection()?;
+    let metadata: Vec<_> = decisionengine::DecisionStrategy::list(&connection)?
+        .iter()
+        .map(decisionengine::DecisionStrategy::metadata)
+        .collect();
+    let body = json!({ "decision_strategies": metadata });
+    Ok(json_response(
+        status::Ok,
+        serde_json::to_string(&body).unwrap(),
+    ))
+}
+
+fn retire_decision_strategy(req: &mut Request, state: &ServerState) -> IronResult<Response> {
+    Ok(handle_retire_decision_strategy(req, state).unwrap_or_else(error_response))
+}
+
+fn handle_retire_decision_strategy(
+    req: &mut Request,
+    state: &ServerState,
+) -> Result<Response, DecisionEngineError> {
+    let id = path_id(req)?;
+    let connection = state.connection()?;
+    let decision_strategy = decisionengine::DecisionStrategy::retire(id, &connection)?;
+    state.strategies.remove(id);
+    Ok(json_response(
+        status::Ok,
+        serde_json::to_string(&decision_strategy.metadata()).unwrap(),
+    ))
+}
+
+fn decision(req: &mut Request, state: &ServerState) -> IronResult<Response> {
+    Ok(handle_decision(req, state).unwrap_or_else(error_response))
+}
+
+fn handle_decision(
+    req: &mut Request,
+    state: &ServerState,
+) -> Result<Response, DecisionEngineError> {
+    let request = match req.get::<bodyparser::Struct<DecisionRequest>>() {
+        Ok(Some(request)) => request,
+        Ok(None) => {
+            return Err(DecisionEngineError::BadRequest(String::from(
+                "Request body is empty.",
+            )))
+        }
+        Err(error) => return Err(DecisionEngineError::BadRequest(error.to_string())),
+    };
+
+    let connection = state.connection()?;
+    let decision_module = state
+        .strategies
+        .get(request.decision_strategy_id, &connection)?;
+
+    let application_data = serde_json::to_value(&request.application_data).unwrap();
+    let mut decision_dataset =
+        decisionengine::datasource::DecisionDataset::new(request.application_data)
+            .with_step_budget(state.step_budget);
+
+    // The trace is stored for every decision, so it is always collected; only
+    // detailed requests return it.
+    let detailed = request.detailed.unwrap_or(false);
+    let mode = if detailed {
+        eval_mode(request.short_circuit.unwrap_or(false))
+    } else {
+        EvalMode::ShortCircuit
+    };
+    let evaluation = decision_module
+        .strategy
+        .eval_detailed(&mut decision_dataset, mode)
+        .and_then(|details| {
+            let outcome = result_name(detailed_result(&details)?);
+            Ok((outcome, serde_json::to_value(&details).unwrap()))
+        });
+    let reasons = serde_json::to_value(decision_dataset.reasons()).unwrap();
+    let (recorded_outcome, result, recorded_details) = recorded_evaluation(&evaluation, &reasons);
+
+    let decision = decisionengine::decisions::NewDecision {
+        decision_strategy_id: request.decision_strategy_id,
+        decision_strategy_hash: decision_module.content_hash.clone(),
+        decided_at: decision_dataset.now(),
+        application_data: application_data,
+        bureau_data: decision_dataset.bureau_data(),
+        outcome: String::from(recorded_outcome),
+        result: result,
+        details: recorded_details,
+    };
+    let decision_id = decision.insert(&connection)?;
+    let (outcome, details) = evaluation?;
+
+    let mut response = json!({
+        "decision_id": decision_id,
+        "result": outcome,
+        "reasons": reasons,
+    });
+    if detailed {
+        response["details"] = details;
+    }
+    Ok(json_response(
+        status::Ok,
+        serde_json::to_string(&response).unwrap(),
+    ))
+}
+
+/// Default and largest number of decisions returned by `GET /decisions`.
+const DEFAULT_DECISION_LIMIT: i64 = 50;
+const MAX_DECISION_LIMIT: i64 = 500;
+
+/// Parses the `:id` segment of the request path.
+fn path_id(req: &Request) -> Result<i32, DecisionEngineError> {
+    let id = req
+        .extensions
+        .get::<Router>()
+        .and_then(|params| params.find("id"))
+        .unwrap_or("");
+    id.parse::<i32>()
+        .map_err(|_| DecisionEngineError::BadRequest(format!("Invalid id {}.", id)))
+}
+
+fn parse_query<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, DecisionEngineError> {
+    value.parse::<T>().map_err(|_| {
+        DecisionEngineError::BadRequest(format!("Invalid value {} for {}.", value, key))
+    })
+}
+
+fn parse_query_datetime(
+    key: &str,
+    value: &str,
+) -> Result<chrono::DateTime<chrono::Utc>, DecisionEngineError> {
+    decisionengine::nodes::parse_datetime(value).ok_or_else(|| {
+        DecisionEngineError::BadRequest(format!("Invalid ISO 8601 datetime {} for {}.", value, key))
+    })
+}
+
+/// Reads the filters of `GET /decisions` from the pairs of its query string.
+fn decision_filter(
+    query: &[(String, String)],
+) -> Result<decisionengine::decisions::DecisionFilter, DecisionEngineError> {
+    let mut filter = decisionengine::decisions::DecisionFilter {
+        decision_strategy_id: None,
+        outcome: None,
+        decided_from: None,
+        decided_to: None,
+        limit: DEFAULT_DECISION_LIMIT,
+        offset: 0,
+    };
+    for &(ref key, ref value) in query {
+        match key.as_str() {
+            "decision_strategy_id" => {
+                filter.decision_strategy_id = Some(parse_query(&key, &value)?)
+            }
+            "outcome" => match value.as_str() {
+                "accept" | "refer" | "reject" | ERROR_OUTCOME => {
+                    filter.outcome = Some(value.clone())
+                }
+                _ => {
+                    return Err(DecisionEngineError::BadRequest(format!(
+                        "Outcome must be accept, refer, reject or error, got {}.",
+                        value
+                    )))
+                }
+            },
+            "from" => filter.decided_from = Some(parse_query_datetime(&key, &value)?),
+            "to" => filter.decided_to = Some(parse_query_datetime(&key, &value)?),
+            "limit" => filter.limit = parse_query(&key, &value)?,
+            "offset" => filter.offset = parse_query(&key, &value)?,
+            _ => {
+                return Err(DecisionEngineError::BadRequest(format!(
+                    "Unknown query parameter {}.",
+                    key
+                )))
             }
         }
-        _ => Ok(Response::with(status::BadRequest)),
     }
+    if filter.limit < 1 || filter.limit > MAX_DECISION_LIMIT {
+        return Err(DecisionEngineError::BadRequest(format!(
+            "Limit must be between 1 and {}.",
+            MAX_DECISION_LIMIT
+        )));
+    }
+    if filter.offset < 0 {
+        return Err(DecisionEngineError::BadRequest(String::from(
+            "Offset must not be negative.",
+        )));
+    }
+    Ok(filter)
+}
+
+fn get_decision(req: &mut Request, state: &ServerState) -> IronResult<Response> {
+    Ok(handle_get_decision(req, state).unwrap_or_else(error_response))
+}
+
+fn handle_get_decision(
+    req: &mut Request,
+    state: &ServerState,
+) -> Result<Response, DecisionEngineError> {
+    let id = path_id(req)?;
+    let connection = state.connection()?;
+    let decision = decisionengine::decisions::Decision::from_id(id, &connection)?;
+    Ok(json_response(
+        status::Ok,
+        serde_json::to_string(&decision).unwrap(),
+    ))
+}
+
+fn list_decisions(req: &mut Request, state: &ServerState) -> IronResult<Response> {
+    Ok(handle_list_decisions(req, state).unwrap_or_else(error_response))
+}
+
+fn handle_list_decisions(
+    req: &mut Request,
+    state: &ServerState,
+) -> Result<Response, DecisionEngineError> {
+    let query: Vec<(String, String)> = req.url.as_ref().query_pairs().into_owned().collect();
+    let filter = decision_filter(&query)?;
+    let connection = state.connection()?;
+    let decisions = decisionengine::decisions::Decision::list(&filter, &connection)?;
+    let body = json!({
+        "decisions": decisions,
+        "limit": filter.limit,
+        "offset": filter.offset,
+    });
+    Ok(json_response(
+        status::Ok,
+        serde_json::to_string(&body).unwrap(),
+    ))
 }
 
 fn server() {
+    if let Err(error) = run_server() {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run_server() -> Result<(), DecisionEngineError> {
+    let state = Arc::new(ServerState {
+        pool: connection_pool()?,
+        strategies: StrategyCache::new(),
+        step_budget: step_budget()?,
+    });
+
     let mut router = Router::new();
-    router.post("/decision", decision, "decision");
+    let decision_state = state.clone();
+    router.post(
+        "/decision",
+        move |req: &mut Request| decision(req, &decision_state),
+        "decision",
+    );
+    let get_decision_state = state.clone();
+    router.get(
+        "/decision/:id",
+        move |req: &mut Request| get_decision(req, &get_decision_state),
+        "decision_get",
+    );
+    let list_decisions_state = state.clone();
+    router.get(
+        "/decisions",
+        move |req: &mut Request| list_decisions(req, &list_decisions_state),
+        "decision_list",
+    );
+    let create_state = state.clone();
     router.post(
         "/decisionstrategy",
-        create_decision_strategy,
+        move |req: &mut Request| create_decision_strategy(req, &create_state),
         "decision_strategy_crate",
     );
+    let get_strategy_state = state.clone();
+    router.get(
+        "/decisionstrategy/:id",
+        move |req: &mut Request| get_decision_strategy(req, &get_strategy_state),
+        "decision_strategy_get",
+    );
+    let list_strategies_state = state.clone();
+    router.get(
+        "/decisionstrategies",
+        move |req: &mut Request| list_decision_strategies(req, &list_strategies_state),
+        "decision_strategy_list",
+    );
+    let retire_strategy_state = state.clone();
+    router.post(
+        "/decisionstrategy/:id/retire",
+        move |req: &mut Request| retire_decision_strategy(req, &retire_strategy_state),
+        "decision_strategy_retire",
+    );
 
     Iron::new(router).http("0.0.0.0:3000").unwrap();
+    Ok(())
 }
 
 fn cli(matches: clap::ArgMatches) {
-    let mut decision_strategy_file =
-        File::open(matches.value_of("ruleset").unwrap()).expect(&format!("Rule file not found"));
+    if let Err(error) = run_cli(matches) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
 
-    let mut decision_module =
-        decisionengine::DecisionEngine::from_file(&mut decision_strategy_file);
+fn run_cli(matches: clap::ArgMatches) -> Result<(), DecisionEngineError> {
+    let ruleset = matches
+        .value_of("ruleset")
+        .ok_or_else(|| DecisionEngineError::BadRequest(String::from("No ruleset given.")))?;
+    let mut decision_strategy_file = File::open(ruleset)
+        .map_err(|error| DecisionEngineError::Io(format!("{}: {}", ruleset, error)))?;
 
-    let input_file_names = matches.values_of("inputs").unwrap();
+    let decision_module = decisionengine::DecisionEngine::from_file(&mut decision_strategy_file)?;
+
+    if matches.is_present("serialize") {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&decision_module.serialize()).unwrap()
+        );
+        return Ok(());
+    }
+
+    let input_file_names = matches
+        .values_of("inputs")
+        .ok_or_else(|| DecisionEngineError::BadRequest(String::from("No inputs given.")))?;
 
     let detailed = matches.is_present("detailed");
+    let mode = eval_mode(matches.is_present("short_circuit"));
+
+    let budget = match matches.value_of("step_budget") {
+        Some(budget) => {
+            parse_step_budget("--step-budget", budget).map_err(DecisionEngineError::BadRequest)?
+        }
+        None => step_budget()?,
+    };
+
+    let now = match matches.value_of("now") {
+        Some(now) => Some(decisionengine::nodes::parse_datetime(now).ok_or_else(|| {
+            DecisionEngineError::BadRequest(format!("Invalid ISO 8601 datetime {}.", now))
+        })?),
+        None => None,
+    };
 
     for input_file_name in input_file_names {
-        let mut input_file =
-            File::open(input_file_name).expect(&format!("File {} not found.", input_file_name));
+        let mut input_file = File::open(input_file_name)
+            .map_err(|error| DecisionEngineError::Io(format!("{}: {}", input_file_name, error)))?;
         let mut inputs = String::new();
-        input_file
-            .read_to_string(&mut inputs)
-            .expect("Something went wrong while reading the input dataset file");
+        input_file.read_to_string(&mut inputs)?;
 
         let input_application_data: decisionengine::datasource::applicationdata::ApplicationDataV1 =
-            match serde_json::from_str(&inputs) {
-                Ok(dd) => dd,
-                _ => panic!("Cannot parse input dataset"),
-            };
+            serde_json::from_str(&inputs).map_err(|error| {
+                DecisionEngineError::BadRequest(format!(
+                    "Cannot parse input dataset {}: {}",
+                    input_file_name, error
+                ))
+            })?;
 
         let mut decision_dataset =
-            decisionengine::datasource::DecisionDataset::new(input_application_data);
+            decisionengine::datasource::DecisionDataset::new(input_application_data)
+                .with_step_budget(budget);
+        if let Some(now) = now {
+            decision_dataset = decision_dataset.with_clock(Box::new(
+                decisionengine::datasource::clock::FixedClock::new(now),
+            ));
+        }
 
-        let result = decision_module.eval(&mut decision_dataset);
+        let details = if detailed {
+            Some(decision_module.eval_detailed(&mut decision_dataset, mode)?)
+        } else {
+            None
+        };
+        let result = match details {
+            Some(ref details) => detailed_result(details)?.clone(),
+            None => decision_module.eval(&mut decision_dataset)?,
+        };
 
         match result {
             decisionengine::EvalResult::Accept => println!("{} [ACCEPT]", input_file_name),
+            decisionengine::EvalResult::Refer => println!("{} [REFER]", input_file_name),
             decisionengine::EvalResult::Reject => println!("{} [REJECT]", input_file_name),
         };
 
-        if detailed {
-            let detailed_result = decisionengine::results::SubmoduleResult::ModuleResult(
-                decisionengine::results::ModuleResult {
-                    module_id: String::from("CBRF Silver"),
-                    result: result.clone(),
-                    submodule_results: Vec::new(),
-                },
-            );
-
-            let mut visitor = decisionengine::visitor::ResultAggregatingVisitor {
-                stack: decisionengine::visitor::ResultStack::new(detailed_result),
-                input: decision_dataset,
-            };
-
-            decision_module.accept(&mut visitor);
-
-            println!(
-                "{}",
-                serde_json::to_string_pretty(visitor.stack.get_result()).unwrap()
-            );
+        for reason in decision_dataset.reasons() {
+            match reason.message {
+                Some(ref message) => println!("    {}: {}", reason.reason_code, message),
+                None => println!("    {}", reason.reason_code),
+            }
+        }
+
+        if let Some(details) = details {
+            println!("{}", serde_json::to_string_pretty(&details).unwrap());
         }
     }
+    Ok(())
 }
 
 fn main() {
@@ -219,6 +649,31 @@ fn main() {
                 .long("detailed")
                 .help("Evaluates entire decision tree without short-circuiting and returns result at all nodes"),
         )
+        .arg(
+            Arg::with_name("short_circuit")
+                .long("short-circuit")
+                .help("With --detailed, stops evaluating each module once its result is decided"),
+        )
+        .arg(
+            Arg::with_name("step_budget")
+                .long("step-budget")
+                .value_name("STEPS")
+                .help("Maximum number of conditions evaluated per decision")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("now")
+                .long("now")
+                .value_name("DATETIME")
+                .help("Evaluates as of the given ISO 8601 datetime instead of the current time")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("serialize")
+                .short("s")
+                .long("serialize")
+                .help("Prints the ruleset in canonical form instead of evaluating inputs"),
+        )
         .arg(
             Arg::with_name("cli")
                 .short("c")
@@ -233,3 +688,144 @@ fn main() {
         cli(matches);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(key, value)| (String::from(key), String::from(value)))
+            .collect()
+    }
+
+    fn bad_request(query: &[(String, String)]) -> String {
+        match decision_filter(query) {
+            Err(DecisionEngineError::BadRequest(message)) => message,
+            _ => panic!("expected a bad request"),
+        }
+    }
+
+    #[test]
+    fn errors_map_to_http_statuses() {
+        let statuses = vec![
+            (DecisionEngineError::StrategyNotFound(1), status::NotFound),
+            (DecisionEngineError::DecisionNotFound(1), status::NotFound),
+            (DecisionEngineError::StrategyRetired(1), status::Gone),
+            (
+                DecisionEngineError::BadRequest(String::from("Invalid id x.")),
+                status::BadRequest,
+            ),
+            (
+                DecisionEngineError::InvalidStrategy(Vec::new()),
+                status::UnprocessableEntity,
+            ),
+            (
+                DecisionEngineError::Config(String::from("DATABASE_URL must be set.")),
+                status::InternalServerError,
+            ),
+        ];
+        for (error, status) in statuses {
+            assert_eq!(error_response(error).status, Some(status));
+        }
+    }
+
+    #[test]
+    fn failed_evaluations_are_recorded_with_their_error() {
+        let reasons = json!([]);
+        let evaluation = Err(DecisionEngineError::StepBudgetExceeded(10));
+        let (outcome, result, details) = recorded_evaluation(&evaluation, &reasons);
+        assert_eq!(outcome, "error");
+        assert_eq!(result, None);
+        assert_eq!(
+            details,
+            json!({ "error": DecisionEngineError::StepBudgetExceeded(10).to_string() })
+        );
+
+        let evaluation = Ok(("refer", json!({ "type": "module" })));
+        let (outcome, result, details) = recorded_evaluation(&evaluation, &reasons);
+        assert_eq!(outcome, "refer");
+        assert_eq!(result, Some(json!({ "result": "refer", "reasons": [] })));
+        assert_eq!(details, json!({ "type": "module" }));
+    }
+
+    #[test]
+    fn step_budgets_are_non_negative_integers() {
+        assert_eq!(parse_step_budget("--step-budget", "1000"), Ok(1000));
+        assert_eq!(
+            parse_step_budget("DECISION_STEP_BUDGET", "-1"),
+            Err(String::from(
+                "DECISION_STEP_BUDGET must be a non-negative integer, got -1."
+            ))
+        );
+    }
+
+    #[test]
+    fn decision_filter_defaults_to_every_decision() {
+        let filter = decision_filter(&[]).unwrap();
+        assert_eq!(filter.decision_strategy_id, None);
+        assert_eq!(filter.outcome, None);
+        assert_eq!(filter.decided_from, None);
+        assert_eq!(filter.decided_to, None);
+        assert_eq!(filter.limit, DEFAULT_DECISION_LIMIT);
+        assert_eq!(filter.offset, 0);
+    }
+
+    #[test]
+    fn decision_filter_reads_every_criterion() {
+        let filter = decision_filter(&query(&[
+            ("decision_strategy_id", "3"),
+            ("outcome", "refer"),
+            ("from", "2018-08-01T00:00:00Z"),
+            ("to", "2018-09-01T00:00:00Z"),
+            ("limit", "10"),
+            ("offset", "20"),
+        ]))
+        .unwrap();
+        assert_eq!(filter.decision_strategy_id, Some(3));
+        assert_eq!(filter.outcome, Some(String::from("refer")));
+        assert_eq!(
+            filter.decided_from.unwrap().to_rfc3339(),
+            "2018-08-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            filter.decided_to.unwrap().to_rfc3339(),
+            "2018-09-01T00:00:00+00:00"
+        );
+        assert_eq!(filter.limit, 10);
+        assert_eq!(filter.offset, 20);
+    }
+
+    #[test]
+    fn decision_filter_rejects_invalid_criteria() {
+        assert_eq!(
+            bad_request(&query(&[("decision_strategy_id", "three")])),
+            "Invalid value three for decision_strategy_id."
+        );
+        assert_eq!(
+            bad_request(&query(&[("outcome", "ACCEPT")])),
+            "Outcome must be accept, refer, reject or error, got ACCEPT."
+        );
+        assert_eq!(
+            bad_request(&query(&[("from", "yesterday")])),
+            "Invalid ISO 8601 datetime yesterday for from."
+        );
+        assert_eq!(
+            bad_request(&query(&[("limit", "501")])),
+            "Limit must be between 1 and 500."
+        );
+        assert_eq!(
+            bad_request(&query(&[("limit", "0")])),
+            "Limit must be between 1 and 500."
+        );
+        assert_eq!(
+            bad_request(&query(&[("offset", "-1")])),
+            "Offset must not be negative."
+        );
+        assert_eq!(
+            bad_request(&query(&[("sort", "outcome")])),
+            "Unknown query parameter sort."
+        );
+    }
+}